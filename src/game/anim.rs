@@ -0,0 +1,251 @@
+//! Animations, and the timeline that plays them.
+//!
+//! Everything that takes time in the game is an [`Entry`] on a [`Timeline`]:
+//! entries play one after another, and game logic waits for them to finish.
+//! An entry can carry [`Track`]s, which animate some effect on one thing on
+//! screen while it plays; rendering asks the timeline how far along they are.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Something on screen that can be animated.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Entity {
+  /// A card, by index.
+  Card(usize),
+}
+
+/// Something that can happen to an [`Entity`] over time.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Effect {
+  /// Turning over, in steps from 0 to `gfx::FLIP_STAGES`.
+  Flip,
+}
+
+/// A value that goes from `from` towards `to` over `duration`, in whole
+/// steps.
+#[derive(Copy, Clone, Debug)]
+pub struct Tween {
+  from: u32,
+  to: u32,
+  duration: Duration,
+}
+
+impl Tween {
+  /// Creates a tween that goes through every value from `from` up to, but not
+  /// including, `to`, spending `duration / (to - from)` on each.
+  pub fn new(from: u32, to: u32, duration: Duration) -> Self {
+    Self { from, to, duration }
+  }
+
+  /// Returns the value `elapsed` into this tween.
+  fn at(self, elapsed: Duration) -> u32 {
+    let span = (self.to - self.from) as u128;
+    let steps = elapsed.as_nanos() * span / self.duration.as_nanos().max(1);
+    self.from + steps.min(span) as u32
+  }
+}
+
+/// Tweens that play one after another.
+#[derive(Clone, Debug, Default)]
+pub struct Sequence(Vec<Tween>);
+
+impl Sequence {
+  /// Creates a sequence that plays `tweens` in order.
+  pub fn new(tweens: impl IntoIterator<Item = Tween>) -> Self {
+    Self(tweens.into_iter().collect())
+  }
+
+  /// Returns how long the whole sequence takes.
+  pub fn duration(&self) -> Duration {
+    self.0.iter().map(|t| t.duration).sum()
+  }
+
+  /// Returns the value `elapsed` into this sequence, or `None` once it's
+  /// over.
+  fn at(&self, mut elapsed: Duration) -> Option<u32> {
+    for &tween in &self.0 {
+      if elapsed < tween.duration {
+        return Some(tween.at(elapsed));
+      }
+      elapsed -= tween.duration;
+    }
+    None
+  }
+}
+
+/// An effect played on one entity during an [`Entry`].
+#[derive(Clone, Debug)]
+pub struct Track {
+  entity: Entity,
+  effect: Effect,
+  /// How long after the entry starts this track starts; until then, it sits
+  /// at its first value.
+  delay: Duration,
+  sequence: Sequence,
+}
+
+impl Track {
+  /// Creates a track that plays `sequence` on `entity` right away.
+  pub fn new(entity: Entity, effect: Effect, sequence: Sequence) -> Self {
+    Self {
+      entity,
+      effect,
+      delay: Duration::ZERO,
+      sequence,
+    }
+  }
+
+  /// Holds this track back until `delay` into its entry.
+  pub fn delay(self, delay: Duration) -> Self {
+    Self { delay, ..self }
+  }
+
+  /// Returns how long after the start of its entry this track ends.
+  fn end(&self) -> Duration {
+    self.delay + self.sequence.duration()
+  }
+}
+
+/// Something on the timeline that takes time.
+#[derive(Clone, Debug)]
+pub struct Entry {
+  duration: Duration,
+  /// Whether any input ends this entry early, rather than being held until
+  /// it's over.
+  pub input_ends: bool,
+  tracks: Vec<Track>,
+}
+
+impl Entry {
+  /// Creates an entry that does nothing for `duration`.
+  pub fn wait(duration: Duration) -> Self {
+    Self {
+      duration,
+      input_ends: false,
+      tracks: Vec::new(),
+    }
+  }
+
+  /// Creates an entry that lasts as long as `tracks` take to play.
+  pub fn play(tracks: impl IntoIterator<Item = Track>) -> Self {
+    let tracks = tracks.into_iter().collect::<Vec<_>>();
+    let duration = tracks.iter().map(Track::end).max().unwrap_or_default();
+    Self {
+      duration,
+      input_ends: false,
+      tracks,
+    }
+  }
+
+  /// Makes this entry last at least `duration`.
+  pub fn at_least(self, duration: Duration) -> Self {
+    Self {
+      duration: self.duration.max(duration),
+      ..self
+    }
+  }
+
+  /// Lets any input end this entry early.
+  pub fn until_input(self) -> Self {
+    Self {
+      input_ends: true,
+      ..self
+    }
+  }
+}
+
+/// Entries waiting to be played, and the one playing now.
+#[derive(Default)]
+pub struct Timeline {
+  entries: VecDeque<Entry>,
+  /// When the first of `entries` started.
+  started: Duration,
+}
+
+impl Timeline {
+  /// Adds `entry` to the end of the timeline, to start once everything before
+  /// it is over; `now` is the current time.
+  pub fn push(&mut self, now: Duration, entry: Entry) {
+    if self.entries.is_empty() {
+      self.started = now;
+    }
+    self.entries.push_back(entry);
+  }
+
+  /// Returns whether nothing is playing or waiting to play.
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Returns the entry playing now.
+  pub fn current(&self) -> Option<&Entry> {
+    self.entries.front()
+  }
+
+  /// Returns whether the entry playing now has run its course by `now`.
+  pub fn is_due(&self, now: Duration) -> bool {
+    self
+      .current()
+      .is_some_and(|e| now.saturating_sub(self.started) >= e.duration)
+  }
+
+  /// Ends the entry playing now, and starts the next one at `now`.
+  pub fn advance(&mut self, now: Duration) {
+    self.entries.pop_front();
+    self.started = now;
+  }
+
+  /// Drops every entry, stopping all animations.
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+
+  /// Returns the value of `effect` on `entity` at `now`, if it's being
+  /// animated.
+  pub fn value(
+    &self,
+    entity: Entity,
+    effect: Effect,
+    now: Duration,
+  ) -> Option<u32> {
+    let track = self
+      .current()?
+      .tracks
+      .iter()
+      .find(|t| t.entity == entity && t.effect == effect)?;
+    let elapsed = now.saturating_sub(self.started);
+    track.sequence.at(elapsed.saturating_sub(track.delay))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tracks_play_their_tweens_in_order() {
+    let ms = Duration::from_millis;
+    let sequence =
+      Sequence::new([Tween::new(0, 2, ms(20)), Tween::new(5, 6, ms(30))]);
+    let entry = Entry::play([
+      Track::new(Entity::Card(0), Effect::Flip, sequence.clone()),
+      Track::new(Entity::Card(1), Effect::Flip, sequence).delay(ms(15)),
+    ]);
+    assert_eq!(entry.duration, ms(65));
+
+    let mut timeline = Timeline::default();
+    timeline.push(ms(100), entry);
+    let value = |idx, at| timeline.value(Entity::Card(idx), Effect::Flip, at);
+    assert_eq!(value(0, ms(109)), Some(0));
+    assert_eq!(value(0, ms(110)), Some(1));
+    assert_eq!(value(0, ms(149)), Some(5));
+    assert_eq!(value(0, ms(150)), None);
+    assert_eq!(value(1, ms(110)), Some(0));
+    assert_eq!(value(1, ms(160)), Some(5));
+    assert_eq!(value(2, ms(110)), None);
+
+    assert!(!timeline.is_due(ms(164)));
+    assert!(timeline.is_due(ms(165)));
+  }
+}
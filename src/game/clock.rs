@@ -0,0 +1,55 @@
+//! Clocks, which animations and waits are timed by.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
+
+/// A source of time for a [`Game`](crate::game::Game).
+///
+/// Animations are timed in real durations rather than frames, so they play at
+/// the same speed whatever the frame rate; swapping out the clock lets time be
+/// controlled by hand instead.
+pub trait Clock {
+  /// Returns how much time has passed since some fixed starting point.
+  fn now(&self) -> Duration;
+}
+
+/// A clock that follows real time.
+pub struct SystemClock {
+  start: Instant,
+}
+
+impl Default for SystemClock {
+  fn default() -> Self {
+    Self {
+      start: Instant::now(),
+    }
+  }
+}
+
+impl Clock for SystemClock {
+  fn now(&self) -> Duration {
+    self.start.elapsed()
+  }
+}
+
+/// A clock that only moves when it's told to.
+///
+/// Clones share the same time, so one can be handed to a game while another is
+/// kept around to move it along.
+#[derive(Clone, Default)]
+pub struct ManualClock(Rc<Cell<Duration>>);
+
+impl ManualClock {
+  /// Moves this clock forward by `by`.
+  pub fn advance(&self, by: Duration) {
+    self.0.set(self.0.get() + by);
+  }
+}
+
+impl Clock for ManualClock {
+  fn now(&self) -> Duration {
+    self.0.get()
+  }
+}
@@ -1,56 +1,127 @@
 // Graphics functions for `Game`.
 
+use std::collections::HashMap;
 use std::iter;
 
 use boxy as b;
 
+use crate::game::anim::Effect;
+use crate::game::anim::Entity;
+use crate::game::solver::Odds;
+use crate::game::CardSize;
 use crate::game::Game;
 use crate::game::Hint;
+use crate::game::HintSide;
+use crate::game::Layout;
+use crate::game::Line;
+use crate::game::State;
+use crate::term::layout::Align;
+use crate::term::layout::Node;
 use crate::term::texel::Color;
 use crate::term::texel::FromChar;
 use crate::term::texel::Style;
 use crate::term::texel::Texel;
+use crate::term::texel::Weight;
 use crate::term::Cell;
 use crate::term::Layer;
 
-// Cards are 9x5; the area that can be drawn on is 5x3, and starts
-// at the coordinate (2, 1).
-// ╭───────╮ ╭───────╮ ╭───────╮ ╭───────╮ ╭───────╮
-// │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │
-// │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │
-// │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │
-// ╰───────╯ ╰───────╯ ╰───────╯ ╰───────╯ ╰───────╯
-pub const CARD_WIDTH: usize = 9;
-pub const CARD_HEIGHT: usize = 5;
-const ART_ORIGIN_WIDTH: usize = 2;
-const ART_ORIGIN_HEIGHT: usize = 1;
-const ART_WIDTH: usize = CARD_WIDTH - ART_ORIGIN_WIDTH * 2;
-const ART_HEIGHT: usize = CARD_HEIGHT - ART_ORIGIN_HEIGHT * 2;
-
-/// Returns an index into a card returned by `new_card` for a coordinate
-/// within the art area.
-fn art_index(x: usize, y: usize) -> usize {
-  (x + ART_ORIGIN_WIDTH) + CARD_WIDTH * (y + ART_ORIGIN_HEIGHT)
+/// The number of steps a card takes to flip over: it narrows for the first
+/// half, and then widens again showing its other side.
+pub const FLIP_STAGES: usize = 9;
+
+/// The size of the art for numbers and Voltorbs drawn by `number_art()`.
+const NUMBER_WIDTH: usize = 5;
+const NUMBER_HEIGHT: usize = 3;
+
+/// The shape of a card at some [`CardSize`].
+///
+/// Normal cards are 9x5; the area that can be drawn on is 5x3, and starts
+/// at the coordinate (2, 1).
+/// ```text
+/// ╭───────╮ ╭───────╮ ╭───────╮ ╭───────╮ ╭───────╮
+/// │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │
+/// │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │
+/// │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │ │ ╱╱╱╱╱ │
+/// ╰───────╯ ╰───────╯ ╰───────╯ ╰───────╯ ╰───────╯
+/// ```
+/// Compact cards are 5x3, with a 3x1 strip to draw on; large cards are 14x8,
+/// with twice the normal area to draw on.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Geometry {
+  width: usize,
+  height: usize,
+  /// The upper-left corner of the area that can be drawn on.
+  art_x: usize,
+  art_y: usize,
+  /// The size of the area that can be drawn on.
+  art_width: usize,
+  art_height: usize,
+}
+
+impl Geometry {
+  fn of(size: CardSize) -> Self {
+    let (width, height, art_x) = match size {
+      CardSize::Compact => (5, 3, 1),
+      CardSize::Normal => (9, 5, 2),
+      CardSize::Large => (14, 8, 2),
+    };
+    Self {
+      width,
+      height,
+      art_x,
+      art_y: 1,
+      art_width: width - art_x * 2,
+      art_height: height - 2,
+    }
+  }
+
+  /// Returns an index into a card returned by `new_card` for a coordinate
+  /// within the art area.
+  fn art_index(self, x: usize, y: usize) -> usize {
+    (x + self.art_x) + self.width * (y + self.art_y)
+  }
+
+  /// Returns how many times bigger than normal art is drawn on these cards.
+  fn scale(self) -> usize {
+    self.art_width / NUMBER_WIDTH
+  }
 }
 
-#[derive(Copy, Clone)]
+/// The styles the game is drawn with; see [`Theme`](crate::game::Theme) for
+/// loading these from a file.
 pub struct Stylesheet {
-  card_style: Style,
-  card_weight: b::Weight,
+  pub(super) card_style: Style,
+  pub(super) card_weight: b::Weight,
+
+  pub(super) selected_style: Style,
+  pub(super) selected_weight: b::Weight,
 
-  selected_style: Style,
-  selected_weight: b::Weight,
+  pub(super) number_style: Style,
+  pub(super) number_weight: b::Weight,
 
-  number_style: Style,
-  number_weight: b::Weight,
+  pub(super) voltorb_red: Style,
+  pub(super) voltorb_wht: Style,
 
-  voltorb_red: Style,
-  voltorb_wht: Style,
+  pub(super) coin_style: Style,
+  pub(super) memo_style: Style,
 
-  coin_style: Style,
-  memo_style: Style,
+  /// Hints cycle through these, row by row and column by column.
+  pub(super) hint_colors: Vec<Style>,
+  /// Hints also cycle through these border weights, so that neighboring hints
+  /// can be told apart without relying on color.
+  pub(super) hint_weights: Vec<b::Weight>,
+  /// Hints also cycle through these marks, drawn in their corners; there may
+  /// be none.
+  pub(super) hint_marks: Vec<char>,
 
-  hint_colors: [Style; 5],
+  /// Heatmap colors for increasing odds of a Voltorb.
+  pub(super) risk_styles: Vec<Style>,
+  /// Marks drawn in the corner of face-down cards in the heatmap, for
+  /// increasing odds of a Voltorb, so that they don't rely on color alone;
+  /// there may be none.
+  pub(super) risk_marks: Vec<char>,
+  pub(super) safe_style: Style,
+  pub(super) doomed_style: Style,
 }
 
 impl Default for Stylesheet {
@@ -58,154 +129,664 @@ impl Default for Stylesheet {
     Self {
       card_style: Color::LtGreen.fg(),
       card_weight: b::Weight::Normal,
-      selected_style: Color::LtCyan.fg(),
+      selected_style: Color::LtCyan.fg().with_weight(Weight::Bold),
       selected_weight: b::Weight::Doubled,
       number_style: Color::LtBlue.fg(),
       number_weight: b::Weight::Thick,
-      voltorb_red: Color::LtRed.fg(),
+      voltorb_red: Color::LtRed.fg().with_weight(Weight::Bold),
       voltorb_wht: Color::LtWhite.fg(),
       coin_style: Color::DkYellow.fg(),
       memo_style: Color::DkYellow.fg(),
-      hint_colors: [
+      hint_colors: vec![
         Color::DkRed.fg(),
         Color::DkGreen.fg(),
         Color::DkYellow.fg(),
         Color::DkBlue.fg(),
         Color::DkMagenta.fg(),
       ],
+      hint_weights: vec![b::Weight::Normal],
+      hint_marks: Vec::new(),
+      risk_styles: vec![
+        Color::DkGreen.fg(),
+        Color::DkYellow.fg(),
+        Color::LtYellow.fg(),
+        Color::LtRed.fg(),
+      ],
+      risk_marks: Vec::new(),
+      safe_style: Color::LtWhite.fg(),
+      doomed_style: Color::DkMagenta.fg(),
+    }
+  }
+}
+
+/// How the outline of a card is drawn.
+#[derive(Copy, Clone, Debug)]
+struct Look {
+  weight: b::Weight,
+  style: Style,
+}
+
+impl Stylesheet {
+  /// Returns the look of an ordinary card.
+  fn card_look(&self) -> Look {
+    Look {
+      weight: self.card_weight,
+      style: self.card_style,
+    }
+  }
+
+  /// Returns the look of the selected card.
+  fn selected_look(&self) -> Look {
+    Look {
+      weight: self.selected_weight,
+      style: self.selected_style,
+    }
+  }
+
+  /// Returns the style for the `idx`th hint along either side of the board.
+  fn hint_style(&self, idx: usize) -> Style {
+    self.hint_colors[idx % self.hint_colors.len()]
+  }
+
+  /// Returns the border weight for the `idx`th hint along either side of the
+  /// board.
+  fn hint_weight(&self, idx: usize) -> b::Weight {
+    self.hint_weights[idx % self.hint_weights.len()]
+  }
+
+  /// Returns the mark for the `idx`th hint along either side of the board, if
+  /// there is one.
+  fn hint_mark(&self, idx: usize) -> Option<char> {
+    let marks = &self.hint_marks;
+    (!marks.is_empty()).then(|| marks[idx % marks.len()])
+  }
+
+  /// Returns the style for a face-down card in the heatmap, given its odds.
+  fn heat_style(&self, odds: &Odds) -> Style {
+    if odds.is_safe() {
+      return self.safe_style;
+    }
+    if odds.is_voltorb() {
+      return self.doomed_style;
+    }
+
+    self.risk_styles[risk_band(odds, self.risk_styles.len())]
+  }
+
+  /// Returns the mark for a face-down card in the heatmap, given its odds, if
+  /// there is one.
+  ///
+  /// Cards that are certainly safe or certainly Voltorbs say so outright, so
+  /// they don't get one.
+  fn heat_mark(&self, odds: &Odds) -> Option<char> {
+    let marks = &self.risk_marks;
+    if marks.is_empty() || odds.is_safe() || odds.is_voltorb() {
+      return None;
+    }
+    Some(marks[risk_band(odds, marks.len())])
+  }
+}
+
+/// Sorts a card into one of `bands` equal bands by its odds of being a
+/// Voltorb.
+fn risk_band(odds: &Odds, bands: usize) -> usize {
+  let band = (odds.voltorb() * bands as f64) as usize;
+  band.min(bands - 1)
+}
+
+/// The width of the controls and scoreboard beside the board.
+const CONTROLS_WIDTH: usize = 32;
+/// The most lines the controls and scoreboard take up.
+const CONTROLS_HEIGHT: usize = 14;
+
+/// The part of the board that is drawn, for boards too big to fit on screen.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Window {
+  /// The first column of cards drawn.
+  col: usize,
+  /// The first row of cards drawn.
+  row: usize,
+  /// The number of columns drawn.
+  cols: usize,
+  /// The number of rows drawn.
+  rows: usize,
+}
+
+impl Window {
+  /// Picks as much of the board as fits in `viewport` when arranged as
+  /// `layout`, panned so that the selected card stays as close to the middle
+  /// as it can.
+  fn new(game: &Game, viewport: Cell, g: Geometry, layout: Layout) -> Self {
+    let (width, height) = game.options.board_dims;
+    let (width, height) = (width as usize, height as usize);
+
+    // See how much of the board fits by laying everything else out around
+    // parts of it, making room for the controls at their tallest.
+    let controls = Cell::from_xy(CONTROLS_WIDTH, CONTROLS_HEIGHT);
+    let size = |cols, rows| {
+      let window = Self {
+        col: 0,
+        row: 0,
+        cols,
+        rows,
+      };
+      arrange(game, g, layout, window, controls).size()
+    };
+    // More cards never take up less room, so these can be binary searches.
+    let cols = (1..=width)
+      .collect::<Vec<_>>()
+      .partition_point(|&cols| size(cols, 1).col() <= viewport.col())
+      .max(1);
+    let rows = (1..=height)
+      .collect::<Vec<_>>()
+      .partition_point(|&rows| size(1, rows).row() <= viewport.row())
+      .max(1);
+
+    let pan = |selected: usize, fits: usize, len: usize| {
+      selected.saturating_sub(fits / 2).min(len - fits)
+    };
+    Self {
+      col: pan(game.selected_card % width, cols, width),
+      row: pan(game.selected_card / width, rows, height),
+      cols,
+      rows,
     }
   }
+
+  /// Returns where the card in column `x` and row `y` goes, relative to the
+  /// window, if it's in it.
+  fn place(self, x: usize, y: usize) -> Option<(usize, usize)> {
+    let x = x.checked_sub(self.col).filter(|&x| x < self.cols)?;
+    let y = y.checked_sub(self.row).filter(|&y| y < self.rows)?;
+    Some((x, y))
+  }
+
+  /// Returns whether the whole board is in this window.
+  fn is_whole(self, game: &Game) -> bool {
+    let (width, height) = game.options.board_dims;
+    (self.cols, self.rows) == (width as usize, height as usize)
+  }
+}
+
+/// Picks the size to draw cards at and how to arrange the screen: whatever
+/// the options ask for, or else the biggest cards that fit the whole board in
+/// `viewport`, in the first layout that fits them.
+fn arrangement(game: &Game, viewport: Cell) -> (Geometry, Layout) {
+  let sizes = match game.options.card_size {
+    Some(size) => vec![size],
+    None => CardSize::ALL.iter().rev().map(|&(size, _)| size).collect(),
+  };
+  let layouts = match game.options.layout {
+    Some(layout) => vec![layout],
+    None => Layout::ALL.iter().map(|&(layout, _)| layout).collect(),
+  };
+
+  let smallest = (Geometry::of(sizes[sizes.len() - 1]), layouts[0]);
+  sizes
+    .iter()
+    .flat_map(|&size| {
+      layouts
+        .iter()
+        .map(move |&layout| (Geometry::of(size), layout))
+    })
+    .find(|&(g, layout)| Window::new(game, viewport, g, layout).is_whole(game))
+    .unwrap_or(smallest)
+}
+
+/// A part of the screen, as far as laying it out goes.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+enum Part {
+  /// A card, by its column and row within the window.
+  Card(usize, usize),
+  /// A column's hint, by its column within the window.
+  ColHint(usize),
+  /// A row's hint, by its row within the window.
+  RowHint(usize),
+  Level,
+  Controls,
 }
 
-pub fn render(
+/// Arranges everything on screen for a board showing `window`, with controls
+/// of the given size.
+fn arrange(
   game: &Game,
+  g: Geometry,
+  layout: Layout,
+  window: Window,
+  controls: Cell,
+) -> Node<Part> {
+  let card = Cell::from_xy(g.width, g.height);
+  let line = |part: fn(usize) -> Part, len| {
+    (0..len).map(move |i| Node::leaf(part(i), card))
+  };
+  let board = Node::column((0..window.rows).map(|y| {
+    Node::row((0..window.cols).map(|x| Node::leaf(Part::Card(x, y), card)))
+      .gap(1)
+  }));
+  let col_hints = Node::row(line(Part::ColHint, window.cols)).gap(1);
+  let row_hints = Node::column(line(Part::RowHint, window.rows));
+  let level = Node::leaf(Part::Level, card);
+
+  // Hints are kept a little apart from the board, so that they don't look
+  // like more cards.
+  let board = match game.options.hint_side {
+    HintSide::BottomRight => Node::column([
+      Node::row([board, row_hints]).gap(2),
+      Node::row([col_hints, level]).gap(2),
+    ]),
+    HintSide::TopLeft => Node::column([
+      Node::row([level, col_hints]).gap(2),
+      Node::row([row_hints, board]).gap(2),
+    ]),
+  }
+  .gap(1);
+
+  let controls = Node::leaf(Part::Controls, controls);
+  match layout {
+    Layout::Landscape => Node::row([board, controls]).gap(2),
+    Layout::Portrait => Node::column([
+      board,
+      controls.pad(Cell::from_xy(0, 1), Cell::from_xy(0, 0)),
+    ])
+    .align(Align::Center),
+  }
+}
+
+/// Something on screen that the player can point at with the mouse.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Target {
+  /// A card on the board, by index.
+  Card(usize),
+  /// The hint at the end of a column or row.
+  Hint(Line),
+}
+
+/// A frame drawn by [`render()`], kept around so that the mouse can be
+/// checked against what's actually on screen.
+pub struct Frame {
+  pub(super) layers: Vec<Layer<'static>>,
+  /// What the ids of `layers` refer to.
+  targets: Vec<Target>,
+  /// Whether the game fit in the viewport; if not, `layers` only asks for a
+  /// bigger terminal.
+  pub(super) fits: bool,
+}
+
+impl Frame {
+  /// Finds what the player is pointing at when they point at `cell`.
+  ///
+  /// Only the topmost layer at `cell` counts, so anything drawn over the board,
+  /// such as a dialog, hides the cards beneath it.
+  pub fn target_at(&self, cell: Cell) -> Option<Target> {
+    Layer::hit_test(&self.layers, cell).map(|id| self.targets[id])
+  }
+}
+
+/// Returns whether `viewport` is big enough to show `game` without clipping
+/// any of it.
+pub fn fits(game: &Game, viewport: Cell, sheet: &Stylesheet) -> bool {
+  let (layers, _, overlays) = compose(game, viewport, sheet);
+  let game_layers = &layers[..layers.len() - overlays];
+  size_of(game_layers).is_some_and(|size| fits_in(size, viewport))
+}
+
+/// Draws `game` to fit in `viewport`.
+///
+/// If the game doesn't fit, this draws a message asking for a bigger terminal
+/// instead, and there is nothing to point at.
+pub fn render(game: &Game, viewport: Cell, sheet: &Stylesheet) -> Frame {
+  let (mut layers, mut targets, overlays) = compose(game, viewport, sheet);
+  let game_layers = layers.len() - overlays;
+
+  // Center everything, unless some of it would be cut off. Overlays don't
+  // count: they're moved back on screen, and clipped if they still don't fit.
+  let mut fits = true;
+  match size_of(&layers[..game_layers]) {
+    Some(size) if !fits_in(size, viewport) => {
+      layers = too_small(size, viewport, sheet);
+      targets.clear();
+      fits = false;
+    }
+    _ => {
+      let (_, lower) = Layer::bounding_box(&layers[..game_layers]);
+      let offset_x = viewport.col().saturating_sub(lower.col()) / 2;
+      let offset_y = viewport.row().saturating_sub(lower.row()) / 2;
+      for (i, layer) in layers.iter_mut().enumerate() {
+        let (mut x, mut y) = layer.origin.xy();
+        x += offset_x;
+        y += offset_y;
+        if i >= game_layers {
+          let rows = layer.data.len() / layer.stride;
+          x = x.min(viewport.col().saturating_sub(layer.stride));
+          y = y.min(viewport.row().saturating_sub(rows));
+        }
+        layer.origin = Cell::from_xy(x, y);
+      }
+    }
+  }
+
+  let traffic = game.options.enable_debugging.then(|| {
+    let t = game.traffic;
+    format!(
+      "bytes/frame: last {}, avg {}, total {} over {} frames",
+      t.last,
+      t.total.checked_div(t.frames).unwrap_or(0),
+      t.total,
+      t.frames,
+    )
+  });
+  for (i, d) in game.debug.iter().chain(&traffic).enumerate() {
+    layers.push(Layer {
+      origin: Cell::from_xy(0, i),
+      stride: viewport.col(),
+      data: sheet
+        .voltorb_red
+        .texels_from_str(d)
+        .collect::<Vec<_>>()
+        .into(),
+      id: None,
+    });
+  }
+
+  Frame {
+    layers,
+    targets,
+    fits,
+  }
+}
+
+/// Returns the number of columns and rows `layers` take up, counting from the
+/// upper-left corner of the screen.
+fn size_of(layers: &[Layer]) -> Option<Cell> {
+  if layers.is_empty() {
+    return None;
+  }
+  let (_, lower) = Layer::bounding_box(layers);
+  Some(Cell::from_xy(lower.col() + 1, lower.row() + 1))
+}
+
+/// Returns whether something of the given `size` fits in `viewport`.
+fn fits_in(size: Cell, viewport: Cell) -> bool {
+  size.col() <= viewport.col() && size.row() <= viewport.row()
+}
+
+/// Draws the message shown in place of the game when the terminal is smaller
+/// than `needed`.
+fn too_small(
+  needed: Cell,
   viewport: Cell,
   sheet: &Stylesheet,
 ) -> Vec<Layer<'static>> {
+  // There may not be room for much, so this is plain text rather than a
+  // dialog, and the most important lines go first.
+  let lines = [
+    "Terminal too small".to_string(),
+    format!("Need {}x{}", needed.col(), needed.row()),
+    format!("Have {}x{}", viewport.col(), viewport.row()),
+    String::new(),
+    "Enlarge it to keep playing,".to_string(),
+    "or press Ctrl-C to quit".to_string(),
+  ];
+  let top = viewport.row().saturating_sub(lines.len()) / 2;
+  lines
+    .iter()
+    .enumerate()
+    .filter(|(_, line)| !line.is_empty())
+    .map(|(i, line)| {
+      let style = if i == 0 {
+        sheet.voltorb_red
+      } else {
+        sheet.coin_style
+      };
+      let width = line.chars().count();
+      Layer {
+        origin: Cell::from_xy(
+          viewport.col().saturating_sub(width) / 2,
+          top + i,
+        ),
+        stride: width,
+        data: style.texels_from_str(line).collect::<Vec<_>>().into(),
+        id: None,
+      }
+    })
+    .collect()
+}
+
+/// Lays out everything in `game` from the upper-left corner of the screen,
+/// also returning the targets that layer ids refer to.
+///
+/// The last few layers, as many as the returned count, are overlays, such as
+/// the statistics screen, that the game doesn't need room for.
+fn compose(
+  game: &Game,
+  viewport: Cell,
+  sheet: &Stylesheet,
+) -> (Vec<Layer<'static>>, Vec<Target>, usize) {
   let mut layers = Vec::new();
+  let mut targets = Vec::new();
 
-  let (width, height) = game.options.board_dims;
-  let width = width as usize;
-  let height = height as usize;
+  let width = game.options.board_dims.0 as usize;
+  let (g, layout) = arrangement(game, viewport);
+  let window = Window::new(game, viewport, g, layout);
+
+  let (controls_stride, controls) = draw_controls(game, window, sheet);
+  let tree = arrange(
+    game,
+    g,
+    layout,
+    window,
+    Cell::from_xy(controls_stride, controls.len() / controls_stride),
+  );
+  let size = tree.size();
+  let origins = tree
+    .place(Cell::from_xy(0, 0))
+    .into_iter()
+    .collect::<HashMap<_, _>>();
+
+  // Dialogs go over the middle of the board.
+  let board = origins[&Part::Card(0, 0)];
+  let board_end = origins[&Part::Card(window.cols - 1, window.rows - 1)];
+  let board_size = Cell::from_xy(
+    board_end.col() + g.width - board.col(),
+    board_end.row() + g.height - board.row(),
+  );
+  let over_board = |stride: usize, rows: usize| {
+    Cell::from_xy(
+      board.col() + board_size.col().saturating_sub(stride) / 2,
+      board.row() + board_size.row().saturating_sub(rows) / 2,
+    )
+  };
 
   // First, draw the cards.
   for (i, card) in game.cards.iter().enumerate() {
-    let mut card_art = new_card(sheet, i == game.selected_card);
-
-    let frames_since = game.frame_num - game.flipping_since;
-    let flip_stage = if frames_since / game.frames_per_flip_step > 4 {
-      frames_since - game.frames_per_flip_step * 5 + 4
-    } else {
-      frames_since / game.frames_per_flip_step
+    let Some((x, y)) = window.place(i % width, i / width) else {
+      continue;
     };
-    let is_flipping = game.flipping_since > 0
-      && game.cards_flipping & (1 << i) != 0
-      && flip_stage < CARD_WIDTH as u64;
+    let flip_stage = game.animation(Entity::Card(i), Effect::Flip);
+    let is_flipping = flip_stage.is_some();
+    let flip_stage = flip_stage.unwrap_or(0);
 
     let mut should_draw_face = card.flipped;
     if is_flipping && flip_stage < 5 {
       should_draw_face ^= true;
     }
 
+    // With the heatmap up, face-down cards are tinted by how likely they are
+    // to be Voltorbs.
+    let analysis = game.analysis.as_ref().filter(|_| !should_draw_face);
+    let odds = analysis.map(|a| a.odds[i]);
+    let mut look = sheet.card_look();
+    if let Some(odds) = &odds {
+      look.style = sheet.heat_style(odds);
+    }
+
+    // Cards in a highlighted row or column take on the color of its hint,
+    // unless the heatmap is already coloring them.
+    if let Some(line) = game.highlight.filter(|l| l.contains(i, width)) {
+      look.weight = sheet.selected_weight;
+      if odds.is_none() {
+        look.style = sheet.hint_style(line.index());
+      }
+    }
+    let outline = if i == game.selected_card {
+      sheet.selected_look()
+    } else {
+      look
+    };
+
+    let mut card_art = new_card(g, outline);
+
     // For each card, if it's been flipped, we draw the contents in the
-    // inner 5x3 box; this is either a number or a Voltorb; otherwise, we
-    // draw the memos.
+    // art area; this is either a number or a Voltorb; otherwise, we draw the
+    // memos.
     draw_card(
+      g,
       &mut card_art,
-      should_draw_face.then(|| card.value),
-      i == game.selected_card,
+      should_draw_face.then_some(card.value),
+      outline.style,
       sheet,
     );
 
-    if !should_draw_face {
-      for i in 0..=9 {
-        if card.memo & (1 << i) != 0 {
-          let c = match i {
-            0 => 'o',
-            i => (b'0' + (i as u8)) as char,
-          };
-          card_art[art_index(i % 3 * 2, i / 3)] =
-            Texel::new(c).with_style(sheet.memo_style);
-        }
+    // The heatmap replaces the memos with the odds of a Voltorb. Only cards
+    // that are certain get a flat 0% or 100%, and estimated odds are marked as
+    // such.
+    if let (Some(analysis), Some(odds)) = (analysis, odds) {
+      let percent = if odds.is_safe() {
+        0
+      } else if odds.is_voltorb() {
+        100
+      } else {
+        ((odds.voltorb() * 100.0).round() as u32).clamp(1, 99)
+      };
+      let mut label = if analysis.exact || percent % 100 == 0 {
+        format!("{percent}%")
+      } else {
+        format!("~{percent}%")
+      };
+      // Compact cards only have room for the number.
+      if label.len() > g.art_width {
+        label = percent.to_string();
+      }
+      for (x, tx) in look
+        .style
+        .texels_from_str(&format!("{label:^0$}", g.art_width))
+        .enumerate()
+      {
+        card_art[g.art_index(x, g.art_height / 2)] = tx;
+      }
+      // Compact cards have no room for the mark beside the odds.
+      if let Some(mark) = sheet.heat_mark(&odds).filter(|_| g.art_height > 1) {
+        card_art[g.art_index(0, 0)] = mark.with_style(look.style);
       }
+    } else if !should_draw_face {
+      draw_memos(g, &mut card_art, card.memo, sheet);
     }
 
     if is_flipping {
+      // Halfway through, a card is squeezed down to its middle column, or
+      // its middle two if its width is even.
       let compress_by = if flip_stage >= 4 {
         8 - flip_stage
       } else {
         flip_stage
-      } as usize;
-      for row in card_art.chunks_mut(CARD_WIDTH) {
+      } as usize
+        * ((g.width - 1) / 2)
+        / 4;
+      for row in card_art.chunks_mut(g.width) {
         row[compress_by] = row[0];
-        row[CARD_WIDTH - compress_by - 1] = row[CARD_WIDTH - 1];
+        row[g.width - compress_by - 1] = row[g.width - 1];
         for i in 0..compress_by {
           row[i] = Texel::empty();
-          row[CARD_WIDTH - i - 1] = Texel::empty();
+          row[g.width - i - 1] = Texel::empty();
         }
       }
     }
 
+    let origin = origins[&Part::Card(x, y)];
     layers.push(Layer {
-      origin: Cell::from_xy(
-        (CARD_WIDTH + 1) * (i % width),
-        CARD_HEIGHT * (i / width),
-      ),
-      stride: CARD_WIDTH,
+      origin,
+      stride: g.width,
       data: card_art.into(),
+      id: Some(targets.len()),
     });
+    targets.push(Target::Card(i));
 
     // In debug mode, draw the zero-index of the card in the corner.
     if game.options.enable_debugging {
+      let label = format!("{:02}", i);
       layers.push(Layer {
         origin: Cell::from_xy(
-          (CARD_WIDTH + 1) * (i % width) + CARD_WIDTH - 2,
-          CARD_HEIGHT * (i / width) + CARD_HEIGHT - 1,
+          origin.col() + g.width - label.len(),
+          origin.row() + g.height - 1,
         ),
-        stride: 2,
+        stride: label.len(),
         data: sheet
           .memo_style
-          .texels_from_str(&format!("{:02}", i))
+          .texels_from_str(&label)
           .collect::<Vec<_>>()
           .into(),
+        // This is part of the card, as far as pointing at it goes.
+        id: Some(targets.len() - 1),
       });
     }
   }
 
-  fn make_hint(hint: Hint, idx: usize, sheet: &Stylesheet) -> Vec<Texel> {
-    let mut sheet = *sheet;
-    sheet.card_style = sheet.hint_colors[idx % sheet.hint_colors.len()];
-    let mut hint_art = new_card(&sheet, false);
-
-    // Draw the small Voltorb.
-    hint_art[art_index(0, 1)] = '▄'.with_style(sheet.voltorb_red);
-    hint_art[art_index(1, 1)] = '▄'.with_style(sheet.voltorb_red);
-    hint_art[art_index(0, 2)] = '▀'.with_style(sheet.voltorb_wht);
-    hint_art[art_index(1, 2)] = '▀'.with_style(sheet.voltorb_wht);
+  fn make_hint(
+    g: Geometry,
+    hint: Hint,
+    idx: usize,
+    highlight: bool,
+    sheet: &Stylesheet,
+  ) -> Vec<Texel> {
+    let mut look = Look {
+      weight: sheet.hint_weight(idx),
+      style: sheet.hint_style(idx),
+    };
+    if highlight {
+      look.weight = sheet.selected_weight;
+    }
+    let mut hint_art = new_card(g, look);
 
-    // Draw the bar separating the two numbers.
+    let sum = format!("{:>1$}", hint.sum, g.art_width);
+    for (i, tx) in sheet.voltorb_wht.texels_from_str(&sum).enumerate() {
+      hint_art[g.art_index(i, 0)] = tx;
+    }
+    // The mark goes before the sum, if the sum leaves room for it.
+    if let Some(mark) = sheet.hint_mark(idx).filter(|_| sum.starts_with(' ')) {
+      hint_art[g.art_index(0, 0)] = mark.with_style(look.style);
+    }
 
-    hint_art[art_index(3, 1)] =
-      b::Char::horizontal(b::Weight::Doubled).with_style(sheet.voltorb_wht);
-    hint_art[art_index(4, 1)] =
-      b::Char::horizontal(b::Weight::Doubled).with_style(sheet.voltorb_wht);
+    // Compact hints have no room for anything else, so the Voltorbs go in the
+    // bottom border.
+    if g.art_height == 1 {
+      let voltorbs = format!("{:>1$}", hint.voltorbs, g.art_width);
+      for (i, c) in voltorbs.chars().enumerate().filter(|(_, c)| *c != ' ') {
+        hint_art[g.art_index(i, 1)] = c.with_style(sheet.voltorb_red);
+      }
+      return hint_art;
+    }
 
-    for (i, tx) in sheet
-      .voltorb_wht
-      .texels_from_str(&format!("{:>5}", hint.sum))
-      .into_iter()
-      .enumerate()
-    {
-      hint_art[art_index(i, 0)] = tx;
+    // Draw the small Voltorb, and the bar separating the two numbers.
+    let mut art = vec![Texel::empty(); NUMBER_WIDTH * NUMBER_HEIGHT];
+    let bar = b::Char::horizontal(b::Weight::Doubled);
+    art[NUMBER_WIDTH] = '▄'.with_style(sheet.voltorb_red);
+    art[NUMBER_WIDTH + 1] = '▄'.with_style(sheet.voltorb_red);
+    art[NUMBER_WIDTH + 3] = bar.with_style(sheet.voltorb_wht);
+    art[NUMBER_WIDTH + 4] = bar.with_style(sheet.voltorb_wht);
+    art[NUMBER_WIDTH * 2] = '▀'.with_style(sheet.voltorb_wht);
+    art[NUMBER_WIDTH * 2 + 1] = '▀'.with_style(sheet.voltorb_wht);
+    if g.scale() > 1 {
+      art = enlarge(&art, NUMBER_WIDTH);
+    }
+    for (y, row) in art.chunks(g.art_width).enumerate().skip(1) {
+      let start = g.art_index(0, y);
+      hint_art[start..start + g.art_width].copy_from_slice(row);
     }
 
-    for (i, tx) in sheet
-      .voltorb_red
-      .texels_from_str(&format!("{:>3}", hint.voltorbs))
-      .into_iter()
-      .enumerate()
-    {
-      hint_art[art_index(i + 2, 2)] = tx;
+    let voltorbs =
+      format!("{:>1$}", hint.voltorbs, g.art_width - 2 * g.scale());
+    for (i, tx) in sheet.voltorb_red.texels_from_str(&voltorbs).enumerate() {
+      hint_art[g.art_index(i + 2 * g.scale(), 2 * g.scale())] = tx;
     }
 
     hint_art
@@ -213,43 +794,146 @@ pub fn render(
 
   // Next, draw the hints along each side.
   for (i, &h) in game.col_hints.iter().enumerate() {
+    let Some((x, _)) = window.place(i, window.row) else {
+      continue;
+    };
     layers.push(Layer {
-      origin: Cell::from_xy(
-        (CARD_WIDTH + 1) * (i % width),
-        CARD_HEIGHT * height + 1,
-      ),
-      stride: 9,
-      data: make_hint(h, i, sheet).into(),
-    })
+      origin: origins[&Part::ColHint(x)],
+      stride: g.width,
+      data: make_hint(g, h, i, game.highlight == Some(Line::Col(i)), sheet)
+        .into(),
+      id: Some(targets.len()),
+    });
+    targets.push(Target::Hint(Line::Col(i)));
   }
 
   for (i, &h) in game.row_hints.iter().enumerate() {
+    let Some((_, y)) = window.place(window.col, i) else {
+      continue;
+    };
     layers.push(Layer {
-      origin: Cell::from_xy((CARD_WIDTH + 1) * width + 1, CARD_HEIGHT * i),
-      stride: 9,
-      data: make_hint(h, i, sheet).into(),
-    })
+      origin: origins[&Part::RowHint(y)],
+      stride: g.width,
+      data: make_hint(g, h, i, game.highlight == Some(Line::Row(i)), sheet)
+        .into(),
+      id: Some(targets.len()),
+    });
+    targets.push(Target::Hint(Line::Row(i)));
   }
 
   // Draw the level number.
-  let mut level = vec![Texel::empty(); CARD_WIDTH * CARD_HEIGHT];
-  draw_card(&mut level, Some(game.level as u8), false, sheet);
+  let mut level = vec![Texel::empty(); g.width * g.height];
+  draw_card(
+    g,
+    &mut level,
+    Some(game.level as u8),
+    sheet.card_style,
+    sheet,
+  );
   layers.push(Layer {
-    origin: Cell::from_xy(
-      (CARD_WIDTH + 1) * width + 1,
-      CARD_HEIGHT * height + 1,
-    ),
-    stride: 9,
+    origin: origins[&Part::Level],
+    stride: g.width,
     data: level.into(),
+    id: None,
   });
 
-  let width_cards_tx = (CARD_WIDTH + 1) * (width + 1);
+  // Ask before letting the player walk away from a round, or before
+  // throwing away a saved game.
+  let prompt = match (game.state, &game.resume) {
+    (State::ConfirmQuit, _) => {
+      Some(vec![format!("Quit and keep {} coins?", game.round_score)])
+    }
+    (State::OfferResume, Some(save)) => Some(vec![
+      "Resume your saved game?".to_string(),
+      format!("Level {}, {} coins", save.level(), save.score()),
+    ]),
+    _ => None,
+  };
+  if let Some(mut lines) = prompt {
+    lines.push("[Y] Yes  ╱╱  [N] No".to_string());
+    let (stride, dialog) = new_dialog(sheet, &lines);
+    let rows = dialog.len() / stride;
+    layers.push(Layer {
+      origin: over_board(stride, rows),
+      stride,
+      data: dialog.into(),
+      id: None,
+    });
+  }
 
   // Draw the controls/scoreboard.
+  layers.push(Layer {
+    origin: origins[&Part::Controls],
+    stride: controls_stride,
+    data: controls.into(),
+    id: None,
+  });
+
+  // The statistics screen goes over everything else.
+  let game_layers = layers.len();
+  if let Some(stats) = &game.stats {
+    let mut lines = vec!["Rounds played, by board".to_string(), String::new()];
+    lines.extend(stats.iter().cloned());
+    lines.push(String::new());
+    lines.push("Press any key to close".to_string());
+
+    let (stride, dialog) = new_dialog(sheet, &lines);
+    let rows = dialog.len() / stride;
+    layers.push(Layer {
+      origin: Cell::from_xy(
+        size.col().saturating_sub(stride) / 2,
+        over_board(stride, rows).row(),
+      ),
+      stride,
+      data: dialog.into(),
+      id: None,
+    });
+  }
+
+  if let Some(settings) = &game.settings {
+    let mut lines = vec!["Settings".to_string(), String::new()];
+    lines.push("Theme".to_string());
+    for (i, theme) in settings.themes.iter().enumerate() {
+      let cursor = if i == settings.cursor { '>' } else { ' ' };
+      let in_use = if *theme == game.options.theme {
+        " (in use)"
+      } else {
+        ""
+      };
+      lines.push(format!("{cursor} {}{in_use}", theme.name()));
+    }
+    lines.push(String::new());
+    lines.push("[Enter] Use  ╱╱  [Esc] Close".to_string());
+
+    let (stride, dialog) = new_dialog(sheet, &lines);
+    let rows = dialog.len() / stride;
+    layers.push(Layer {
+      origin: Cell::from_xy(
+        size.col().saturating_sub(stride) / 2,
+        over_board(stride, rows).row(),
+      ),
+      stride,
+      data: dialog.into(),
+      id: None,
+    });
+  }
+
+  let overlays = layers.len() - game_layers;
+  (layers, targets, overlays)
+}
+
+/// Draws the controls and scoreboard, returning their stride and texels.
+fn draw_controls(
+  game: &Game,
+  window: Window,
+  sheet: &Stylesheet,
+) -> (usize, Vec<Texel>) {
   let mut controls = Vec::new();
-  let bar = iter::repeat(b::Char::horizontal(b::Weight::Doubled).into_char())
-    .take(32)
-    .collect::<String>();
+  let bar = iter::repeat_n(
+    b::Char::horizontal(b::Weight::Doubled).into_char(),
+    CONTROLS_WIDTH,
+  )
+  .collect::<String>();
   controls.extend(sheet.coin_style.texels_from_str(&bar));
   controls.extend(
     sheet
@@ -261,6 +945,26 @@ pub fn render(
       .coin_style
       .texels_from_str(" [Enter] Flip  ╱╱  [Q]   Quit   "),
   );
+  controls.extend(
+    sheet
+      .coin_style
+      .texels_from_str(" [H]     Odds  ╱╱  [B]   Bank   "),
+  );
+  controls.extend(
+    sheet
+      .coin_style
+      .texels_from_str(" [S]     Save  ╱╱  [L]   Load   "),
+  );
+  controls.extend(
+    sheet
+      .coin_style
+      .texels_from_str(" [T]     Stats ╱╱  [R]   Theme  "),
+  );
+  controls.extend(
+    sheet
+      .coin_style
+      .texels_from_str(" [O]  Settings ╱╱               "),
+  );
   controls.extend(sheet.coin_style.texels_from_str(&bar));
   controls.extend(
     sheet
@@ -271,361 +975,520 @@ pub fn render(
     " {:.>13} ╱╱ {:.>13} ",
     game.round_score, game.score
   )));
+  controls.extend(
+    sheet
+      .coin_style
+      .texels_from_str(&format!(" Seed {:.>25} ", game.seed)),
+  );
+  // Say which part of the board is showing, if it doesn't all fit.
+  if !window.is_whole(game) {
+    let shown = format!(
+      "Showing cols {}-{}, rows {}-{}",
+      window.col + 1,
+      window.col + window.cols,
+      window.row + 1,
+      window.row + window.rows,
+    );
+    controls.extend(
+      sheet
+        .coin_style
+        .texels_from_str(&format!(" {shown:<30.30} ")),
+    );
+  }
   controls.extend(sheet.coin_style.texels_from_str(&bar));
-  layers.push(Layer {
-    origin: Cell::from_xy(width_cards_tx + 2, 0),
-    stride: bar.chars().count(),
-    data: controls.into(),
-  });
-
-  // Center everything.
-  let (_, lower) = Layer::bounding_box(&layers);
-  let offset_x = viewport.col().saturating_sub(lower.col()) / 2;
-  let offset_y = viewport.row().saturating_sub(lower.row()) / 2;
-
-  for layer in &mut layers {
-    layer.origin = Cell::from_xy(
-      layer.origin.col() + offset_x,
-      layer.origin.row() + offset_y,
+  if let Some(notice) = &game.notice {
+    controls.extend(
+      sheet
+        .coin_style
+        .texels_from_str(&format!(" {notice:<30.30} ")),
     );
   }
+  (bar.chars().count(), controls)
+}
 
-  for (i, d) in game.debug.iter().enumerate() {
-    layers.push(Layer {
-      origin: Cell::from_xy(0, i),
-      stride: viewport.col(),
-      data: sheet
-        .voltorb_red
-        .texels_from_str(d)
-        .collect::<Vec<_>>()
-        .into(),
-    });
+/// Returns the upper-left corner of a box.
+///
+/// boxy draws the thick corner as `┎`, with a light right arm, so that one is
+/// spelled out by hand.
+fn upper_left(weight: b::Weight, style: Style) -> Texel {
+  if weight == b::Weight::Thick {
+    return '┏'.with_style(style);
   }
+  b::Char::upper_left(weight)
+    .style(b::Style::Curved)
+    .with_style(style)
+}
+
+/// Creates a new blank card, outlined with `look`.
+fn new_card(g: Geometry, look: Look) -> Vec<Texel> {
+  let Look {
+    weight: b_weight,
+    style: tx_style,
+  } = look;
+  let horizontal = b::Char::horizontal(b_weight).with_style(tx_style);
+  let vertical = b::Char::vertical(b_weight).with_style(tx_style);
+  let corner = |c: b::Char| c.style(b::Style::Curved).with_style(tx_style);
 
-  layers
+  let mut card = Vec::with_capacity(g.width * g.height);
+  card.push(upper_left(b_weight, tx_style));
+  card.extend(iter::repeat_n(horizontal, g.width - 2));
+  card.push(corner(b::Char::upper_right(b_weight)));
+  for _ in 2..g.height {
+    card.push(vertical);
+    card.extend(iter::repeat_n(Texel::empty(), g.width - 2));
+    card.push(vertical);
+  }
+  card.push(corner(b::Char::lower_left(b_weight)));
+  card.extend(iter::repeat_n(horizontal, g.width - 2));
+  card.push(corner(b::Char::lower_right(b_weight)));
+  card
 }
 
-/// Creates a new blank card.
-fn new_card(sheet: &Stylesheet, selected: bool) -> Vec<Texel> {
-  let (b_weight, tx_style) = if selected {
-    (sheet.selected_weight, sheet.selected_style)
-  } else {
-    (sheet.card_weight, sheet.card_style)
+/// Creates a box around some lines of text, returning its stride and texels.
+fn new_dialog(sheet: &Stylesheet, lines: &[String]) -> (usize, Vec<Texel>) {
+  let (b_weight, tx_style) = (sheet.selected_weight, sheet.selected_style);
+  let inner = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) + 2;
+  let bar = || {
+    iter::repeat_n(b::Char::horizontal(b_weight).with_style(tx_style), inner)
   };
 
-  vec![
-    b::Char::upper_left(b_weight)
-      .style(b::Style::Curved)
-      .with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
+  let mut dialog = Vec::new();
+  dialog.push(upper_left(b_weight, tx_style));
+  dialog.extend(bar());
+  dialog.push(
     b::Char::upper_right(b_weight)
       .style(b::Style::Curved)
       .with_style(tx_style),
-    //
-    b::Char::vertical(b_weight).with_style(tx_style),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    b::Char::vertical(b_weight).with_style(tx_style),
-    //
-    b::Char::vertical(b_weight).with_style(tx_style),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    b::Char::vertical(b_weight).with_style(tx_style),
-    //
-    b::Char::vertical(b_weight).with_style(tx_style),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    Texel::empty(),
-    b::Char::vertical(b_weight).with_style(tx_style),
-    //
+  );
+
+  for line in lines {
+    dialog.push(b::Char::vertical(b_weight).with_style(tx_style));
+    dialog.extend(
+      sheet
+        .coin_style
+        .texels_from_str(&format!(" {line:<0$} ", inner - 2)),
+    );
+    dialog.push(b::Char::vertical(b_weight).with_style(tx_style));
+  }
+
+  dialog.push(
     b::Char::lower_left(b_weight)
       .style(b::Style::Curved)
       .with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
-    b::Char::horizontal(b_weight).with_style(tx_style),
+  );
+  dialog.extend(bar());
+  dialog.push(
     b::Char::lower_right(b_weight)
       .style(b::Style::Curved)
       .with_style(tx_style),
-  ]
+  );
+
+  (inner + 2, dialog)
 }
 
-/// Draws art on a card: a backside, a Voltorb, or a number.
+/// Draws art on a card: a backside in the style `back`, a Voltorb, or a
+/// number.
 fn draw_card(
-  card: &mut Vec<Texel>,
+  g: Geometry,
+  card: &mut [Texel],
   n: Option<u8>,
-  selected: bool,
+  back: Style,
   sheet: &Stylesheet,
 ) {
-  for (i, line) in card
-    .chunks_mut(CARD_WIDTH)
-    .skip(ART_ORIGIN_HEIGHT)
-    .take(ART_HEIGHT)
-    .enumerate()
-  {
-    let line = &mut line[ART_ORIGIN_WIDTH..][..ART_WIDTH];
+  let Some(n) = n else {
+    for y in 0..g.art_height {
+      let start = g.art_index(0, y);
+      card[start..start + g.art_width].fill('╱'.with_style(back));
+    }
+    return;
+  };
 
-    if n.is_none() {
-      line.fill('╱'.with_style(if selected {
-        sheet.selected_style
-      } else {
-        sheet.card_style
-      }));
-      continue;
+  // Compact cards only have room for a single glyph.
+  if g.art_height == 1 {
+    card[g.art_index(g.art_width / 2, 0)] = match n {
+      0 => '●'.with_style(sheet.voltorb_red),
+      n => ((b'0' + n) as char).with_style(sheet.number_style),
+    };
+    return;
+  }
+
+  let mut art = (0..NUMBER_HEIGHT)
+    .flat_map(|row| number_art(n, row, sheet))
+    .collect::<Vec<_>>();
+  if g.scale() > 1 {
+    art = enlarge(&art, NUMBER_WIDTH);
+  }
+  for (y, row) in art.chunks(g.art_width).enumerate() {
+    let start = g.art_index(0, y);
+    card[start..start + g.art_width].copy_from_slice(row);
+  }
+}
+
+/// Draws the memos the player has set on a face-down card.
+fn draw_memos(g: Geometry, card: &mut [Texel], memo: u16, sheet: &Stylesheet) {
+  let memos = (0..=9).filter(|i| memo & (1 << i) != 0).map(|i| {
+    let c = match i {
+      0 => 'o',
+      i => (b'0' + (i as u8)) as char,
+    };
+    (i, c.with_style(sheet.memo_style))
+  });
+
+  // Compact cards line memos up in a strip, and mark any that don't fit.
+  if g.art_height == 1 {
+    let mut strip = memos.map(|(_, tx)| tx).collect::<Vec<_>>();
+    if strip.len() > g.art_width {
+      strip.truncate(g.art_width - 1);
+      strip.push('+'.with_style(sheet.memo_style));
     }
+    for (x, tx) in strip.into_iter().enumerate() {
+      card[g.art_index(x, 0)] = tx;
+    }
+    return;
+  }
 
-    let art = match (n.unwrap(), i) {
-      (0, 0) => [
-        Texel::empty(),
-        '▁'.with_style(sheet.voltorb_red),
-        '▁'.with_style(sheet.voltorb_red),
-        '▁'.with_style(sheet.voltorb_red),
-        Texel::empty(),
-      ],
-      (0, 1) => [
-        Texel::empty(),
-        '▛'.with_style(sheet.voltorb_red),
-        '█'.with_style(sheet.voltorb_red),
-        '▜'.with_style(sheet.voltorb_red),
-        Texel::empty(),
-      ],
-      (0, 2) => [
-        Texel::empty(),
-        '▀'.with_style(sheet.voltorb_wht),
-        '▀'.with_style(sheet.voltorb_wht),
-        '▀'.with_style(sheet.voltorb_wht),
-        Texel::empty(),
-      ],
-      (1, 0) => [
-        Texel::empty(),
-        b::Char::right_half(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::upper_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-        Texel::empty(),
-      ],
-      (1, 1) => [
-        Texel::empty(),
-        Texel::empty(),
-        b::Char::vertical(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-        Texel::empty(),
-      ],
-      (1, 2) => [
-        Texel::empty(),
-        b::Char::right_half(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::up_tee(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::left_half(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (2, 0) => [
-        Texel::empty(),
-        b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::upper_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (2, 1) => [
-        Texel::empty(),
-        b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::lower_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (2, 2) => [
-        Texel::empty(),
-        b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::left_half(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (3, 0) => [
-        Texel::empty(),
-        b::Char::right_half(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::upper_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (3, 1) => [
-        Texel::empty(),
-        Texel::empty(),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::left_tee(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (3, 2) => [
-        Texel::empty(),
-        b::Char::right_half(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::lower_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (4, 0) => [
-        Texel::empty(),
-        b::Char::down_half(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-        b::Char::down_half(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (4, 1) => [
-        Texel::empty(),
-        b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::cross(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (4, 2) => [
-        Texel::empty(),
-        Texel::empty(),
-        Texel::empty(),
-        b::Char::up_half(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (5, 0) => [
-        Texel::empty(),
-        b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::left_half(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (5, 1) => [
-        Texel::empty(),
-        b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::upper_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (5, 2) => [
-        Texel::empty(),
-        b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::lower_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (6, 0) => [
-        Texel::empty(),
-        b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::upper_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (6, 1) => [
-        Texel::empty(),
-        b::Char::right_tee(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::upper_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (6, 2) => [
-        Texel::empty(),
-        b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::lower_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (7, 0) => [
-        Texel::empty(),
-        b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::upper_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (7, 1) => [
-        Texel::empty(),
-        Texel::empty(),
-        Texel::empty(),
-        b::Char::vertical(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (7, 2) => [
-        Texel::empty(),
-        Texel::empty(),
-        Texel::empty(),
-        b::Char::up_half(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (8, 0) => [
-        Texel::empty(),
-        b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::upper_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (8, 1) => [
-        Texel::empty(),
-        b::Char::right_tee(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::left_tee(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (8, 2) => [
-        Texel::empty(),
-        b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::lower_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (9, 0) => [
-        Texel::empty(),
-        b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::upper_right(sheet.number_weight)
-          .with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (9, 1) => [
-        Texel::empty(),
-        b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
-        b::Char::left_tee(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      (9, 2) => [
-        Texel::empty(),
-        Texel::empty(),
-        Texel::empty(),
-        b::Char::up_half(sheet.number_weight).with_style(sheet.number_style),
-        Texel::empty(),
-      ],
-      x => panic!("bad card data: {x:?}"),
+  let scale = g.scale();
+  for (i, tx) in memos {
+    card[g.art_index(i % 3 * 2 * scale, i / 3 * scale)] = tx;
+  }
+}
+
+/// Scales `art`, which is `stride` texels wide, up to twice its size.
+///
+/// Lines drawn with box-drawing characters stay connected, and blocks stay
+/// solid.
+fn enlarge(art: &[Texel], stride: usize) -> Vec<Texel> {
+  let lines = art
+    .iter()
+    .map(|tx| tx.glyph().and_then(box_char))
+    .collect::<Vec<_>>();
+
+  let mut out = vec![Texel::empty(); art.len() * 4];
+  for (i, &tx) in art.iter().enumerate() {
+    let (x, y) = (i % stride * 2, i / stride * 2);
+    // Arms that don't meet anything are only stubs, and stay that way.
+    let right = (i % stride + 1 < stride)
+      .then(|| lines[i + 1])
+      .flatten()
+      .is_some_and(|c| c.left.is_some());
+    let down = lines.get(i + stride).copied().flatten();
+    let down = down.is_some_and(|c| c.up.is_some());
+
+    let [ul, ur, ll, lr] = double(tx, right, down);
+    out[y * stride * 2 + x] = ul;
+    out[y * stride * 2 + x + 1] = ur;
+    out[(y + 1) * stride * 2 + x] = ll;
+    out[(y + 1) * stride * 2 + x + 1] = lr;
+  }
+  out
+}
+
+/// Returns the 2x2 block of texels that `tx` becomes at twice the size, in
+/// reading order.
+///
+/// `right` and `down` say whether `tx` connects to its neighbors in those
+/// directions, if it's a box-drawing character.
+fn double(tx: Texel, right: bool, down: bool) -> [Texel; 4] {
+  let Some(glyph) = tx.glyph() else {
+    return [Texel::empty(); 4];
+  };
+  let style = tx.style();
+
+  // Each quadrant of a block element becomes a whole block.
+  let quadrants = match glyph {
+    '▘' => Some(0b0001),
+    '▝' => Some(0b0010),
+    '▀' => Some(0b0011),
+    '▖' => Some(0b0100),
+    '▌' => Some(0b0101),
+    '▞' => Some(0b0110),
+    '▛' => Some(0b0111),
+    '▗' => Some(0b1000),
+    '▚' => Some(0b1001),
+    '▐' => Some(0b1010),
+    '▜' => Some(0b1011),
+    '▄' => Some(0b1100),
+    '▙' => Some(0b1101),
+    '▟' => Some(0b1110),
+    '█' => Some(0b1111),
+    _ => None,
+  };
+  if let Some(quadrants) = quadrants {
+    return [0b0001, 0b0010, 0b0100, 0b1000].map(|q| {
+      if quadrants & q != 0 {
+        '█'.with_style(style)
+      } else {
+        Texel::empty()
+      }
+    });
+  }
+
+  // A line along the bottom stays along the bottom, twice as thick.
+  if glyph == '▁' {
+    let line = '▂'.with_style(style);
+    return [Texel::empty(), Texel::empty(), line, line];
+  }
+
+  // A box-drawing character stays in the upper left, and its right and down
+  // arms are drawn out to meet whatever they connected to.
+  if let Some(c) = box_char(glyph) {
+    let arm = |weight: Option<b::Weight>, line: fn(b::Weight) -> b::Char| {
+      weight.map_or(Texel::empty(), |w| line(w).with_style(style))
     };
+    return [
+      tx,
+      if right {
+        arm(c.right, b::Char::horizontal)
+      } else {
+        Texel::empty()
+      },
+      if down {
+        arm(c.down, b::Char::vertical)
+      } else {
+        Texel::empty()
+      },
+      Texel::empty(),
+    ];
+  }
+
+  [tx, Texel::empty(), Texel::empty(), Texel::empty()]
+}
+
+/// Finds the box-drawing character that `glyph` is, if it has arms of a single
+/// weight.
+fn box_char(glyph: char) -> Option<b::Char> {
+  let weights = [b::Weight::Normal, b::Weight::Thick, b::Weight::Doubled];
+  weights
+    .into_iter()
+    .flat_map(|w| {
+      (1..16u8).map(move |arms| {
+        let arm = |bit: u8| (arms & bit != 0).then_some(w);
+        b::Char {
+          up: arm(1),
+          right: arm(2),
+          down: arm(4),
+          left: arm(8),
+          ..b::Char::default()
+        }
+      })
+    })
+    .find(|c| c.into_char() == glyph)
+}
 
-    line.copy_from_slice(&art);
+/// Returns row `row` of the art for the number `n`, or a Voltorb if `n` is
+/// zero.
+fn number_art(n: u8, row: usize, sheet: &Stylesheet) -> [Texel; NUMBER_WIDTH] {
+  match (n, row) {
+    (0, 0) => [
+      Texel::empty(),
+      '▁'.with_style(sheet.voltorb_red),
+      '▁'.with_style(sheet.voltorb_red),
+      '▁'.with_style(sheet.voltorb_red),
+      Texel::empty(),
+    ],
+    (0, 1) => [
+      Texel::empty(),
+      '▛'.with_style(sheet.voltorb_red),
+      '█'.with_style(sheet.voltorb_red),
+      '▜'.with_style(sheet.voltorb_red),
+      Texel::empty(),
+    ],
+    (0, 2) => [
+      Texel::empty(),
+      '▀'.with_style(sheet.voltorb_wht),
+      '▀'.with_style(sheet.voltorb_wht),
+      '▀'.with_style(sheet.voltorb_wht),
+      Texel::empty(),
+    ],
+    (1, 0) => [
+      Texel::empty(),
+      b::Char::right_half(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::upper_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+      Texel::empty(),
+    ],
+    (1, 1) => [
+      Texel::empty(),
+      Texel::empty(),
+      b::Char::vertical(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+      Texel::empty(),
+    ],
+    (1, 2) => [
+      Texel::empty(),
+      b::Char::right_half(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::up_tee(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::left_half(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (2, 0) => [
+      Texel::empty(),
+      b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::upper_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (2, 1) => [
+      Texel::empty(),
+      b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::lower_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (2, 2) => [
+      Texel::empty(),
+      b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::left_half(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (3, 0) => [
+      Texel::empty(),
+      b::Char::right_half(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::upper_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (3, 1) => [
+      Texel::empty(),
+      Texel::empty(),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::left_tee(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (3, 2) => [
+      Texel::empty(),
+      b::Char::right_half(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::lower_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (4, 0) => [
+      Texel::empty(),
+      b::Char::down_half(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+      b::Char::down_half(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (4, 1) => [
+      Texel::empty(),
+      b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::cross(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (4, 2) => [
+      Texel::empty(),
+      Texel::empty(),
+      Texel::empty(),
+      b::Char::up_half(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (5, 0) => [
+      Texel::empty(),
+      b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::left_half(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (5, 1) => [
+      Texel::empty(),
+      b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::upper_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (5, 2) => [
+      Texel::empty(),
+      b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::lower_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (6, 0) => [
+      Texel::empty(),
+      b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::upper_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (6, 1) => [
+      Texel::empty(),
+      b::Char::right_tee(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::upper_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (6, 2) => [
+      Texel::empty(),
+      b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::lower_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (7, 0) => [
+      Texel::empty(),
+      b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::upper_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (7, 1) => [
+      Texel::empty(),
+      Texel::empty(),
+      Texel::empty(),
+      b::Char::vertical(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (7, 2) => [
+      Texel::empty(),
+      Texel::empty(),
+      Texel::empty(),
+      b::Char::up_half(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (8, 0) => [
+      Texel::empty(),
+      b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::upper_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (8, 1) => [
+      Texel::empty(),
+      b::Char::right_tee(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::left_tee(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (8, 2) => [
+      Texel::empty(),
+      b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::lower_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (9, 0) => [
+      Texel::empty(),
+      b::Char::upper_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::upper_right(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (9, 1) => [
+      Texel::empty(),
+      b::Char::lower_left(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::horizontal(sheet.number_weight).with_style(sheet.number_style),
+      b::Char::left_tee(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    (9, 2) => [
+      Texel::empty(),
+      Texel::empty(),
+      Texel::empty(),
+      b::Char::up_half(sheet.number_weight).with_style(sheet.number_style),
+      Texel::empty(),
+    ],
+    x => panic!("bad card data: {x:?}"),
   }
 }
@@ -0,0 +1,27 @@
+//! Board data from Pokémon HeartGold and SoulSilver.
+//!
+//! See <https://bulbapedia.bulbagarden.net/wiki/Voltorb_Flip#Levels>.
+//!
+//! Only the card counts are reproduced. HGSS also re-deals a board when too
+//! many of its multipliers are "free", in a row or column with no Voltorbs,
+//! but the cards here are placed anywhere, so a board dealt from these tables
+//! may be one that HGSS would never deal: typically an easier one.
+
+use crate::game::MAX_LEVEL;
+
+/// The boards HGSS can deal at each level, as the number of x2 cards, x3
+/// cards, and Voltorbs; the rest of the board is filled with 1s.
+///
+/// HGSS picks one of a level's five layouts uniformly at random. The most a
+/// board can pay out is the product of its multipliers, which grows with the
+/// level, from 24 coins up to 3456.
+pub const LAYOUTS: [[(usize, usize, usize); 5]; MAX_LEVEL] = [
+  [(3, 1, 6), (0, 3, 6), (5, 0, 6), (2, 2, 6), (4, 1, 6)],
+  [(1, 3, 7), (6, 0, 7), (3, 2, 7), (0, 4, 7), (5, 1, 7)],
+  [(2, 3, 8), (7, 0, 8), (4, 2, 8), (1, 4, 8), (6, 1, 8)],
+  [(3, 3, 8), (0, 5, 8), (8, 0, 10), (5, 2, 10), (2, 4, 10)],
+  [(7, 1, 10), (4, 3, 10), (1, 5, 10), (9, 0, 10), (6, 2, 10)],
+  [(3, 4, 10), (0, 6, 10), (8, 1, 10), (5, 3, 10), (2, 5, 10)],
+  [(7, 2, 10), (4, 4, 10), (1, 6, 13), (9, 1, 13), (6, 3, 10)],
+  [(0, 7, 10), (8, 2, 10), (5, 4, 10), (2, 6, 10), (7, 3, 10)],
+];
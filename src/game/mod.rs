@@ -1,29 +1,196 @@
 //! Game logic.
 
+use std::borrow::Cow;
 use std::collections::VecDeque;
+use std::str::FromStr;
+use std::time::Duration;
 
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 use rand::Rng;
+use rand::SeedableRng;
+use serde::Deserialize;
+use serde::Serialize;
 
+use crate::game::anim::Effect;
+use crate::game::anim::Entity;
+use crate::game::anim::Entry;
+use crate::game::anim::Sequence;
+use crate::game::anim::Timeline;
+use crate::game::anim::Track;
+use crate::game::anim::Tween;
 use crate::term::Cell;
 use crate::term::Event;
 use crate::term::Key;
 use crate::term::Layer;
 use crate::term::Mod;
+use crate::term::MouseAction;
 
+mod anim;
+mod clock;
 mod gfx;
+mod hgss;
+mod save;
+mod sim;
+mod solver;
+mod stats;
+#[cfg(test)]
+mod tests;
+mod theme;
+
+pub use clock::*;
+pub use save::*;
+pub use sim::*;
+pub use stats::*;
+pub use theme::*;
 
 // Options for configuring a [`Game`].
 pub struct Options {
-  // The dimensions of the board. Values must be in `5..=8`.
+  // The dimensions of the board. Values must be in `3..=16`.
   pub board_dims: (u32, u32),
   // Maximum value for a multiplier card. Values must be in `3..=9`.
   pub max_card_value: u8,
+  /// Seed for the first round's board; if `None`, one is chosen at random.
+  ///
+  /// Each subsequent round's seed is derived from this one, so a whole session
+  /// can be replayed given the same seed and the same moves.
+  pub seed: Option<u64>,
+  /// How boards are generated.
+  pub generator: Generator,
+  /// Whether to keep a record of each finished round on disk.
+  pub record_rounds: bool,
+  /// The theme to draw the game with.
+  pub theme: Theme,
+  /// How big to draw cards; if `None`, the biggest size that fits is used.
+  pub card_size: Option<CardSize>,
+  /// How to arrange the board and the controls; if `None`, whichever fits the
+  /// biggest cards is used.
+  pub layout: Option<Layout>,
+  /// Which sides of the board the hints go on.
+  pub hint_side: HintSide,
   /// Enables debug output.
   pub enable_debugging: bool,
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+/// A way of generating boards.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Generator {
+  /// A formula that approximates HGSS, generalized to any board size and card
+  /// value.
+  Formula,
+  /// The card counts from HGSS, placed freely; only meaningful for 5x5
+  /// boards with cards up to 3. See the `hgss` module for how this differs
+  /// from the real game.
+  Hgss,
+}
+
+impl Generator {
+  const ALL: [(Generator, &'static str); 2] =
+    [(Self::Formula, "formula"), (Self::Hgss, "hgss")];
+
+  /// Returns this generator's name on the command line.
+  pub fn name(self) -> &'static str {
+    Self::ALL.iter().find(|(g, _)| *g == self).unwrap().1
+  }
+}
+
+impl FromStr for Generator {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    parse_name(&Self::ALL, s)
+  }
+}
+
+/// Looks up `s` in a table of command-line names, like the `ALL` tables of the
+/// enums here; the error lists every name there is.
+fn parse_name<T: Copy>(all: &[(T, &str)], s: &str) -> Result<T, String> {
+  match all.iter().find(|(_, name)| *name == s) {
+    Some(&(value, _)) => Ok(value),
+    None => {
+      let names = all.iter().map(|(_, name)| *name).collect::<Vec<_>>();
+      Err(format!("expected one of: {}", names.join(", ")))
+    }
+  }
+}
+
+/// A size to draw cards at.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CardSize {
+  /// 5x3 cards, with a single glyph for a number.
+  Compact,
+  /// 9x5 cards.
+  Normal,
+  /// 14x8 cards, with numbers drawn twice as big.
+  Large,
+}
+
+impl CardSize {
+  /// All sizes, from smallest to largest.
+  const ALL: [(CardSize, &'static str); 3] = [
+    (Self::Compact, "compact"),
+    (Self::Normal, "normal"),
+    (Self::Large, "large"),
+  ];
+}
+
+impl FromStr for CardSize {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    parse_name(&Self::ALL, s)
+  }
+}
+
+/// A way of arranging the board and the controls on screen.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Layout {
+  /// The controls go to the right of the board.
+  Landscape,
+  /// The controls go under the board, for tall terminals.
+  Portrait,
+}
+
+impl Layout {
+  /// All layouts, in the order they're tried when none is asked for.
+  const ALL: [(Layout, &'static str); 2] =
+    [(Self::Landscape, "landscape"), (Self::Portrait, "portrait")];
+}
+
+impl FromStr for Layout {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    parse_name(&Self::ALL, s)
+  }
+}
+
+/// The sides of the board that hints go on.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HintSide {
+  /// Column hints below the board, and row hints to its right, as in HGSS.
+  BottomRight,
+  /// Column hints above the board, and row hints to its left, as in most
+  /// nonogram-style puzzles.
+  TopLeft,
+}
+
+impl HintSide {
+  const ALL: [(HintSide, &'static str); 2] = [
+    (Self::BottomRight, "bottom-right"),
+    (Self::TopLeft, "top-left"),
+  ];
+}
+
+impl FromStr for HintSide {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    parse_name(&Self::ALL, s)
+  }
+}
+
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 struct Card {
   /// The value of the card from 0 to 9; zero is a Voltorb.
   value: u8,
@@ -33,7 +200,7 @@ struct Card {
   memo: u16,
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 struct Hint {
   /// The sum of cards along a row/column.
   sum: u32,
@@ -41,14 +208,6 @@ struct Hint {
   voltorbs: u32,
 }
 
-#[derive(Copy, Clone, Debug, Default)]
-struct Wait {
-  /// The number of frames to wait.
-  wait_for: u64,
-  /// If true, any input will end the wait instead of being ignored.
-  input_ends_wait: bool,
-}
-
 /// The big ol' game state struct.
 ///
 /// This contains all state for the current game.
@@ -59,6 +218,17 @@ pub struct Game {
   level: u32,
   score: u64,
   round_score: u64,
+  /// The number of rounds in a row won by flipping at least
+  /// `STREAK_MIN_FLIPPED` cards.
+  streak: u32,
+
+  /// The seed used to generate the current round's board. Each round's seed
+  /// is drawn from the one before it, so this is all it takes to carry on
+  /// dealing the same boards.
+  seed: u64,
+  /// Generator for all randomness within a round; reseeded from `seed` when
+  /// the board is generated.
+  rng: StdRng,
 
   cards: Vec<Card>,
   col_hints: Vec<Hint>,
@@ -68,45 +238,156 @@ pub struct Game {
   selected_card: usize,
   frame_num: u64,
   state: State,
-  /// Whether to wait, preempting game logic for some number of frames.
-  waits: Vec<Wait>,
-
-  /// Bitset of which cards are currently flipping. Cards will animate towards
-  /// the value of `flipped`, i.e., a card with `flipped` set will appear to
-  /// flip face-up.
-  cards_flipping: u64,
-  /// The frame cards have been flipping since, if any. Cards can only flip
-  /// in batches.
-  flipping_since: u64,
-  /// The number of frames between each frame of flipping.
-  frames_per_flip_step: u64,
+  /// Animations and waits, which preempt game logic until they're over.
+  timeline: Timeline,
+  /// What animations and waits are timed by.
+  clock: Box<dyn Clock>,
+  /// Input that came in during a wait, to be handled once it's over.
+  queued: VecDeque<Event>,
+
+  /// Whether to tint face-down cards by the odds that they are Voltorbs.
+  heatmap: bool,
+  /// The odds for the board as the player currently sees it; only kept up to
+  /// date while `heatmap` is set.
+  analysis: Option<solver::Analysis>,
+
+  /// A saved game the player has been offered to resume.
+  resume: Option<Save>,
+  /// A message for the player, shown until the next key press.
+  notice: Option<String>,
+  /// The statistics screen, as lines of text, while it is open.
+  stats: Option<Vec<String>>,
+  /// The settings screen, while it is open.
+  settings: Option<theme::Settings>,
+
+  /// A row or column the player has picked out by clicking its hint.
+  highlight: Option<Line>,
+  /// The size of the terminal; `None` until the game is told, such as when
+  /// nobody is watching.
+  viewport: Option<Cell>,
+  /// Whether the game fit in the terminal when it was last resized or
+  /// rendered.
+  fits: bool,
+  /// The last frame rendered, for working out what the mouse is over.
+  frame: Option<gfx::Frame>,
+  /// Whether the game looks any different since the last frame was rendered.
+  dirty: bool,
+  /// The styles loaded from `options.theme`.
+  sheet: gfx::Stylesheet,
 
   debug: VecDeque<String>,
+  /// How much rendering has been sending to the terminal.
+  traffic: Traffic,
+}
+
+/// Counts of the bytes rendering sends to the terminal, for the debug output.
+#[derive(Copy, Clone, Debug, Default)]
+struct Traffic {
+  /// The bytes sent by the last frame that sent anything.
+  last: usize,
+  /// The bytes sent over all frames.
+  total: u64,
+  /// The number of frames that sent anything.
+  frames: u64,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 enum State {
   /// A new game needs to be generated.
   NewGame,
+  /// The player is being asked whether to resume a saved game.
+  OfferResume,
   /// The game is currently actively being played.
   Standby,
+  /// The player asked to quit the round and keep their coins, and needs to
+  /// confirm it.
+  ConfirmQuit,
   /// Check the result of a card getting flipped over.
   FlipCheck,
   /// Indicates that a game ended; this does scoring and proceeds to
   /// NewGame.
-  GameOver { new_level: u32, win: bool },
+  GameOver { new_level: u32, outcome: Outcome },
+}
+
+/// A row or column of the board.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Line {
+  Row(usize),
+  Col(usize),
+}
+
+impl Line {
+  /// Returns the index of this row or column.
+  fn index(self) -> usize {
+    match self {
+      Self::Row(i) | Self::Col(i) => i,
+    }
+  }
+
+  /// Returns whether the card at `idx` lies on this line, for a board that is
+  /// `stride` cards wide.
+  fn contains(self, idx: usize, stride: usize) -> bool {
+    match self {
+      Self::Row(y) => idx / stride == y,
+      Self::Col(x) => idx % stride == x,
+    }
+  }
+}
+
+/// How a round ended.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+enum Outcome {
+  /// Every multiplier was flipped; the coins are kept.
+  Win,
+  /// A Voltorb was flipped; the coins are lost.
+  Loss,
+  /// The player quit; the coins are kept.
+  Quit,
 }
 
 const MAX_LEVEL: usize = 8;
 
+/// How long each step of a card flip takes, normally.
+const FLIP_STEP: Duration = Duration::from_millis(33);
+/// How long each step of the first half of a slow flip takes, for suspense.
+const SLOW_FLIP_STEP: Duration = Duration::from_millis(333);
+/// How long the board stays revealed after a round, unless the player moves on.
+const REVEAL_TIME: Duration = Duration::from_secs(5);
+/// How far apart each diagonal of the board starts turning over when the whole
+/// board flips.
+const FLIP_ALL_STAGGER: Duration = Duration::from_millis(20);
+/// The most input events that are held on to during a wait; mashing keys
+/// beyond this shouldn't leave the game replaying them for ages.
+const MAX_QUEUED: usize = 16;
+
+/// The number of consecutive qualifying wins needed to reach `MAX_LEVEL`;
+/// otherwise, winning can only go up to the level below it.
+const STREAK_FOR_MAX_LEVEL: u32 = 5;
+/// The number of cards a win needs to flip to count towards the streak.
+const STREAK_MIN_FLIPPED: u32 = 8;
+
 impl Game {
   /// Create a new game state.
   pub fn new(options: Options) -> Self {
+    Self::with_clock(options, Box::<SystemClock>::default())
+  }
+
+  /// Create a new game state, timed by `clock`.
+  pub fn with_clock(options: Options, clock: Box<dyn Clock>) -> Self {
     let (x, y) = options.board_dims;
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let (sheet, notice) = match options.theme.load() {
+      Ok(sheet) => (sheet, None),
+      Err(e) => (gfx::Stylesheet::default(), Some(e)),
+    };
     Self {
       level: 1,
       score: 0,
       round_score: 0,
+      streak: 0,
+
+      seed,
+      rng: StdRng::seed_from_u64(seed),
 
       cards: vec![Card::default(); (x as usize) * (y as usize)],
       col_hints: vec![Hint::default(); x as usize],
@@ -115,21 +396,152 @@ impl Game {
       selected_card: 0,
       state: State::NewGame,
       debug: VecDeque::new(),
-      waits: Vec::new(),
+      traffic: Traffic::default(),
+      timeline: Timeline::default(),
+      clock,
+      queued: VecDeque::new(),
 
       frame_num: 0,
-      cards_flipping: 0,
-      flipping_since: 0,
-      frames_per_flip_step: 1,
+
+      heatmap: false,
+      analysis: None,
+
+      resume: None,
+      notice,
+      stats: None,
+      settings: None,
+
+      highlight: None,
+      viewport: None,
+      fits: true,
+      frame: None,
+      dirty: true,
+      sheet,
 
       options,
     }
   }
 
+  /// Tells the game how big the terminal is, so that mouse input lines up with
+  /// what was rendered, and so that input can be held off while the game
+  /// doesn't fit.
+  pub fn resize(&mut self, viewport: Cell) {
+    self.viewport = Some(viewport);
+    self.fits = gfx::fits(self, viewport, &self.sheet);
+    self.dirty = true;
+  }
+
+  /// Returns whether the game looks any different than it did when it was
+  /// last rendered.
+  pub fn needs_render(&self) -> bool {
+    self.dirty
+  }
+
+  /// Returns whether the game has nothing left to do until the player does
+  /// something: no animation is playing and nothing is being waited on.
+  ///
+  /// While this is true, calling [`Game::interact()`] without an event does
+  /// nothing, so the caller can block until there is one.
+  pub fn is_idle(&self) -> bool {
+    self.timeline.is_empty()
+      && self.queued.is_empty()
+      && matches!(
+        self.state,
+        State::Standby | State::ConfirmQuit | State::OfferResume
+      )
+  }
+
+  /// Returns the current value of `effect` on `entity`, if it's being
+  /// animated.
+  fn animation(&self, entity: Entity, effect: Effect) -> Option<u32> {
+    self.timeline.value(entity, effect, self.clock.now())
+  }
+
+  /// Holds on to `event` until the current wait is over, if it's the kind of
+  /// input that's worth keeping: moving the selection, flipping, and memos.
+  ///
+  /// Anything else, such as opening a menu halfway through a flip, is more
+  /// likely to surprise the player than help them, so it's dropped, with a
+  /// notice so that they know to press it again.
+  fn enqueue(&mut self, event: Option<Event>) {
+    let keep = match event {
+      Some(Event::Key { key, .. }) => matches!(
+        key,
+        Key::Left
+          | Key::Right
+          | Key::Up
+          | Key::Down
+          | Key::Enter
+          | Key::Glyph('0'..='9')
+      ),
+      Some(Event::Mouse { action, .. }) => matches!(
+        action,
+        MouseAction::Press | MouseAction::ScrollUp | MouseAction::ScrollDown
+      ),
+      _ => false,
+    };
+    if keep {
+      self.queue_behind(event);
+    } else if matches!(event, Some(Event::Key { .. })) {
+      self.notice = Some("Busy: try that key again.".to_string());
+    }
+  }
+
+  /// Holds on to `event` until everything queued before it has gone, whatever
+  /// it is; used once the wait is over and the queue is draining, since the
+  /// player can see what the input ahead of it does by then.
+  ///
+  /// Only mouse motion is dropped, since it means nothing once the mouse has
+  /// moved on.
+  fn queue_behind(&mut self, event: Option<Event>) {
+    let keep = match event {
+      Some(Event::Key { .. }) => true,
+      Some(Event::Mouse { action, .. }) => matches!(
+        action,
+        MouseAction::Press | MouseAction::ScrollUp | MouseAction::ScrollDown
+      ),
+      _ => false,
+    };
+    if keep && self.queued.len() < MAX_QUEUED {
+      self.queued.extend(event);
+    }
+  }
+
+  /// Plays `entry` once everything before it on the timeline is over.
+  fn play(&mut self, entry: Entry) {
+    self.timeline.push(self.clock.now(), entry);
+  }
+
   /// Renders the current game state as a pile of layers that can be handed off
   /// to the compositor.
-  pub fn render(&self, viewport: Cell) -> Vec<Layer> {
-    gfx::render(self, viewport, &gfx::Stylesheet::default())
+  pub fn render(&mut self, viewport: Cell) -> Vec<Layer<'_>> {
+    let frame = gfx::render(self, viewport, &self.sheet);
+    self.fits = frame.fits;
+    self.dirty = false;
+
+    // Keep the frame, so that the mouse is checked against exactly what the
+    // player sees.
+    let frame = self.frame.insert(frame);
+    frame
+      .layers
+      .iter()
+      .map(|l| Layer {
+        origin: l.origin,
+        stride: l.stride,
+        data: Cow::Borrowed(&l.data),
+        id: l.id,
+      })
+      .collect()
+  }
+
+  /// Tells the game how many bytes the last frame took to send, so that the
+  /// debug output can show it.
+  pub fn frame_sent(&mut self, bytes: usize) {
+    if bytes > 0 {
+      self.traffic.last = bytes;
+      self.traffic.total += bytes as u64;
+      self.traffic.frames += 1;
+    }
   }
 
   fn debug(&mut self, val: impl FnOnce() -> String) {
@@ -143,22 +555,107 @@ impl Game {
 
   /// Generates a new game board in-place.
   ///
+  /// The board is a pure function of `seed`, `level`, and `options`.
+  fn generate_board(&mut self) {
+    self.rng = StdRng::seed_from_u64(self.seed);
+
+    let seed = self.seed;
+    self.debug(|| format!("generating new game with seed {seed}..."));
+
+    let (voltorbs, cards) = match self.options.generator {
+      Generator::Formula => self.formula_cards(),
+      Generator::Hgss => self.hgss_cards(),
+    };
+
+    // At this point, we have a game formed, so the rest of this function is
+    // just building out the relevant data structures.
+
+    self.cards.fill(Card {
+      value: 1,
+      ..Card::default()
+    });
+
+    let mut indices = (0usize..self.cards.len()).collect::<Vec<_>>();
+    indices.shuffle(&mut self.rng);
+
+    self.debug(|| format!("voltorbs: {:?}", &indices[..voltorbs]));
+    for index in &indices[..voltorbs] {
+      self.cards[*index].value = 0;
+    }
+
+    self.debug(|| {
+      let zip = Iterator::zip(indices[voltorbs..].iter(), cards.iter());
+      let pairs = zip.map(|(i, c)| format!("{i}: {c}")).collect::<Vec<_>>();
+      format!("cards: {{{}}}", pairs.join(", "))
+    });
+    for (index, card) in Iterator::zip(indices[voltorbs..].iter(), cards.iter())
+    {
+      self.cards[*index].value = *card as u8;
+    }
+
+    self.round_score = 0;
+    self.row_hints.fill(Hint::default());
+    self.col_hints.fill(Hint::default());
+
+    let stride = self.options.board_dims.0 as usize;
+    for (i, card) in self.cards.iter().enumerate() {
+      self.row_hints[i / stride].sum += card.value as u32;
+      self.col_hints[i % stride].sum += card.value as u32;
+      if card.value == 0 {
+        self.row_hints[i / stride].voltorbs += 1;
+        self.col_hints[i % stride].voltorbs += 1;
+      }
+    }
+
+    // Solving a fresh board can take a moment, so only do it if someone is
+    // going to look at the result.
+    if self.options.enable_debugging {
+      let analysis = solver::solve(
+        &self.options,
+        &self.cards,
+        &self.row_hints,
+        &self.col_hints,
+      );
+      self.debug(|| {
+        let Some(analysis) = analysis else {
+          return "odds: no consistent board".to_string();
+        };
+        let odds = &analysis.odds;
+        let count =
+          |f: fn(&solver::Odds) -> bool| odds.iter().filter(|o| f(o)).count();
+        let multipliers =
+          odds.iter().map(solver::Odds::multiplier).sum::<f64>();
+        let risk = odds.iter().map(solver::Odds::voltorb).fold(1.0, f64::min);
+        let best = odds.iter().map(solver::Odds::expected).fold(0.0, f64::max);
+        format!(
+          "odds: exact: {}, safe: {}, voltorbs: {}, useless: {}, \
+           multipliers: {multipliers:.1}, min risk: {risk:.3}, best: {best:.2}",
+          analysis.exact,
+          count(solver::Odds::is_safe),
+          count(solver::Odds::is_voltorb),
+          count(solver::Odds::is_useless),
+        )
+      });
+    }
+  }
+
+  /// Picks the number of Voltorbs and the multiplier cards for a new board.
+  ///
   /// This function uses a formula, rather than a table like HGSS, which allows
   /// it to be generalized to larger widths and card values. It approximates the
   /// HGSS data for dims = 5x5 and max_card = 3, although not exactly.
-  fn generate_board(&mut self) {
+  fn formula_cards(&mut self) -> (usize, Vec<u32>) {
     let max_card = self.options.max_card_value as u32;
     let avg_width = (self.options.board_dims.0 + self.options.board_dims.1) / 2;
-    let mut rng = rand::thread_rng();
-
-    self.debug(|| "generating new game...".to_string());
     self.debug(|| format!("avg_width: {avg_width}"));
 
     // The number of Voltorbs is approximately a linear function of the area,
     // so regardless of size the Voltorbs make up a consistent fraction of the
-    // board at a particular level.
+    // board at a particular level. Boards three cards wide still get more
+    // Voltorbs as the level goes up.
+    let per_level = (avg_width - 3).max(1);
     let voltorbs = (self.cards.len() / 5 * 2)
-      .min((self.level * (avg_width - 3)) as usize + self.cards.len() / 5);
+      .min((self.level * per_level) as usize + self.cards.len() / 5);
     self.debug(|| format!("voltorbs: {voltorbs}"));
 
     // The sum of all multiplier cards is a generalization of the formula
@@ -167,9 +664,21 @@ impl Game {
     // The level used for the computation is either `level` or `level - 1/2`,
     // chosen at random.
     let mut sum = self.level * (max_card - 1) + 3 * max_card;
-    if rng.gen::<bool>() {
+    if self.rng.gen::<bool>() {
       sum -= (max_card - 1) / 2;
     }
+
+    // That formula was tuned for boards from 5x5 to 8x8. Smaller boards scale
+    // it down by area, so that they aren't left with nothing but Voltorbs and
+    // multipliers, and bigger ones scale it up, so that there's still something
+    // worth finding among all the 1s. There's always room for at least one
+    // multiplier.
+    let area = self.cards.len() as u32;
+    if area < 25 {
+      sum = (sum * area / 25).max(3);
+    } else if area > 64 {
+      sum = sum * area / 64;
+    }
     self.debug(|| format!("sum: {sum}"));
 
     // Separately, we compute the maximum payout for this round; this keeps the
@@ -190,9 +699,6 @@ impl Game {
     // would not cause the prefix product of payouts to overflow max, and pick
     // one randomly. We subtract it from the sum, add it to the payout, and
     // repeat.
-    //
-    // At this point, we have a game formed, so the rest of this function is
-    // just building out the relevant data structures.
     let mut cards = Vec::new();
     let mut coins = 1;
     while sum > 1 && cards.len() < self.cards.len() - voltorbs {
@@ -203,7 +709,7 @@ impl Game {
         break;
       }
 
-      let value = rng.gen_range(2..=max_candidate.unwrap());
+      let value = self.rng.gen_range(2..=max_candidate.unwrap());
       coins *= value as u64;
       sum -= value;
       cards.push(value);
@@ -211,79 +717,172 @@ impl Game {
     self.debug(|| format!("cards: {cards:?}"));
     self.debug(|| format!("coins: {coins}"));
 
-    self.cards.fill(Card {
-      value: 1,
-      ..Card::default()
-    });
+    (voltorbs, cards)
+  }
 
-    let mut indices = (0usize..self.cards.len()).collect::<Vec<_>>();
-    indices.shuffle(&mut rng);
+  /// Picks the number of Voltorbs and the multiplier cards for a new board
+  /// from the HGSS tables.
+  fn hgss_cards(&mut self) -> (usize, Vec<u32>) {
+    let layouts = &hgss::LAYOUTS[self.level as usize - 1];
+    let (twos, threes, voltorbs) =
+      layouts[self.rng.gen_range(0..layouts.len())];
+    self.debug(|| format!("layout: {twos}x2, {threes}x3, {voltorbs}x0"));
 
-    self.debug(|| format!("voltorbs: {:?}", &indices[..voltorbs]));
-    for index in &indices[..voltorbs] {
-      self.cards[*index].value = 0;
-    }
+    let mut cards = vec![2; twos];
+    cards.resize(twos + threes, 3);
+    (voltorbs, cards)
+  }
 
-    self.debug(|| {
-      let zip = Iterator::zip(indices[voltorbs..].iter(), cards.iter());
-      let pairs = zip.map(|(i, c)| format!("{i}: {c}")).collect::<Vec<_>>();
-      format!("cards: {{{}}}", pairs.join(", "))
-    });
-    for (index, card) in Iterator::zip(indices[voltorbs..].iter(), cards.iter())
-    {
-      self.cards[*index].value = *card as u8;
+  /// Recomputes the odds shown by the heatmap, if it is enabled.
+  ///
+  /// This needs to be called whenever a card is flipped face-up.
+  fn analyze(&mut self) {
+    self.analysis = None;
+    if self.heatmap {
+      self.analysis = solver::solve(
+        &self.options,
+        &self.cards,
+        &self.row_hints,
+        &self.col_hints,
+      );
     }
+  }
 
-    self.round_score = 0;
-    self.row_hints.fill(Hint::default());
-    self.col_hints.fill(Hint::default());
+  /// Ends the current round, revealing the board.
+  ///
+  /// The next level follows HGSS: a win goes up one level, but the top level
+  /// is only reached after a streak of wins that each flipped enough cards.
+  /// Otherwise, the level drops to the number of cards the player flipped, if
+  /// that is lower.
+  fn end_round(&mut self, outcome: Outcome) {
+    let flipped = self
+      .cards
+      .iter()
+      .filter(|c| c.flipped && c.value != 0)
+      .count() as u32;
+    let new_level = if outcome == Outcome::Win {
+      if flipped >= STREAK_MIN_FLIPPED {
+        self.streak += 1;
+      } else {
+        self.streak = 0;
+      }
 
-    let stride = self.options.board_dims.0 as usize;
-    for (i, card) in self.cards.iter().enumerate() {
-      self.row_hints[i / stride].sum += card.value as u32;
-      self.col_hints[i % stride].sum += card.value as u32;
-      if card.value == 0 {
-        self.row_hints[i / stride].voltorbs += 1;
-        self.col_hints[i % stride].voltorbs += 1;
+      // Winning never costs a level, even at the top level without a streak.
+      if self.streak >= STREAK_FOR_MAX_LEVEL {
+        MAX_LEVEL as u32
+      } else {
+        self.level.max((self.level + 1).min(MAX_LEVEL as u32 - 1))
+      }
+    } else {
+      self.streak = 0;
+      flipped.min(self.level)
+    };
+
+    self.debug(|| format!("{outcome:?} after {flipped} flips"));
+    if self.options.record_rounds {
+      let coins = if outcome == Outcome::Loss {
+        0
+      } else {
+        self.round_score
+      };
+      let record =
+        Record::new(&self.options, self.level, outcome, coins, flipped);
+      if let Err(e) = record.append() {
+        self.notice = Some(format!("Could not record round: {e}"));
       }
     }
+    // Whatever the player queued up was meant for the board that just ended.
+    self.queued.clear();
+    self.state = State::GameOver { new_level, outcome };
+    self.flip_all(true);
+    self.play(Entry::wait(REVEAL_TIME).until_input());
   }
 
+  /// Flips the selected card and checks the result, unless it's already face
+  /// up.
+  fn flip(&mut self, slow: bool) {
+    if self.cards[self.selected_card].flipped {
+      return;
+    }
+
+    // If only one card remains to be flipped, make this a slow flip 10% of the
+    // time.
+    let remaining = self.cards.iter().filter(|c| c.value > 1).count();
+    let slow = (remaining == 1 && self.rng.gen_bool(0.1)) || slow;
+
+    self.state = State::FlipCheck;
+    self.flip_selected(true, slow);
+  }
+
+  /// Steps the selected card's memo through single marks: none, then Voltorb,
+  /// then each card value in turn, and back around.
+  ///
+  /// A memo with several marks steps on from its highest one.
+  fn cycle_memo(&mut self, forward: bool) {
+    let steps = self.options.max_card_value as u16 + 2;
+    let card = &mut self.cards[self.selected_card];
+    let current = (u16::BITS - card.memo.leading_zeros()) as u16;
+    let next = if forward {
+      (current + 1) % steps
+    } else {
+      (current + steps - 1) % steps
+    };
+    card.memo = if next == 0 { 0 } else { 1 << (next - 1) };
+  }
+
+  /// Turns every card to `flipped`, in a wave from the upper-left corner.
   fn flip_all(&mut self, flipped: bool) {
-    let mut cards_flipping = 0;
+    let stride = self.options.board_dims.0 as usize;
+    let mut tracks = Vec::new();
     for (i, card) in self.cards.iter_mut().enumerate() {
-      if card.flipped != flipped {
-        cards_flipping |= 1 << i;
+      if card.flipped == flipped {
+        continue;
       }
       card.flipped = flipped;
+      let diagonal = (i % stride + i / stride) as u32;
+      tracks.push(flip_track(i, FLIP_STEP).delay(FLIP_ALL_STAGGER * diagonal));
     }
-
-    self.frames_per_flip_step = 1;
-    self.cards_flipping = cards_flipping;
-    self.flipping_since = self.frame_num;
-    self.waits.push(Wait {
-      wait_for: gfx::CARD_WIDTH as u64,
-      input_ends_wait: false,
-    });
+    self.play(Entry::play(tracks));
   }
 
+  /// Turns the selected card to `flipped`, pausing a moment afterwards so the
+  /// player can see what it was.
   fn flip_selected(&mut self, flipped: bool, slow: bool) {
     self.cards[self.selected_card].flipped = flipped;
 
-    self.frames_per_flip_step = if slow { 10 } else { 1 };
-    self.cards_flipping = 1 << self.selected_card;
-    self.flipping_since = self.frame_num;
-    self.waits.push(Wait {
-      wait_for: self.frames_per_flip_step * (gfx::CARD_WIDTH / 2 + 1) as u64
-        + gfx::CARD_WIDTH as u64,
-      input_ends_wait: false,
-    });
+    let step = if slow { SLOW_FLIP_STEP } else { FLIP_STEP };
+    self.play(
+      Entry::play([flip_track(self.selected_card, step)]).at_least(
+        step * (gfx::FLIP_STAGES / 2 + 1) as u32
+          + FLIP_STEP * gfx::FLIP_STAGES as u32,
+      ),
+    );
   }
 
   /// Presents a player interaction to the game.
   ///
   /// Returns whether the game loop should continue.
   pub fn interact(&mut self, event: Option<Event>) -> bool {
+    let selected = self.selected_card;
+    let running = self.handle(event);
+
+    // Pointing at things only changes the selection, so moving the mouse
+    // around doesn't need a new frame unless it does.
+    let pointing = matches!(
+      event,
+      Some(Event::Mouse {
+        action: MouseAction::Move | MouseAction::Drag,
+        ..
+      })
+    );
+    if !pointing || self.selected_card != selected || !self.is_idle() {
+      self.dirty = true;
+    }
+    running
+  }
+
+  /// Does the work of `interact()`.
+  fn handle(&mut self, mut event: Option<Event>) -> bool {
     self.frame_num += 1;
     let stride = self.options.board_dims.0 as usize;
     if event.is_some() {
@@ -297,28 +896,113 @@ impl Game {
       return false;
     }
 
-    if let Some(wait) = self.waits.first_mut() {
-      if wait.wait_for == 0 || (wait.input_ends_wait && event.is_some()) {
-        self.waits.remove(0);
-        if !self.waits.is_empty() {
+    // While the terminal is too small to show the game, the player can't see
+    // what their input would do, so it's dropped until they make it bigger.
+    if event.is_some() && self.viewport.is_some() && !self.fits {
+      return true;
+    }
+
+    // Only presses count as the player doing something; merely moving the
+    // mouse shouldn't dismiss anything.
+    let pressed = matches!(
+      event,
+      Some(
+        Event::Key { .. }
+          | Event::Mouse {
+            action: MouseAction::Press,
+            ..
+          }
+      )
+    );
+    if pressed {
+      self.notice = None;
+
+      // The statistics screen sits on top of everything else, and any key
+      // closes it.
+      if self.stats.take().is_some() {
+        return true;
+      }
+      if self.settings.is_some() {
+        if let Some(Event::Key { key, .. }) = event {
+          self.settings_key(key);
+        }
+        return true;
+      }
+    }
+
+    // Quitting doesn't wait its turn behind animations or buffered input.
+    let quit = matches!(
+      event,
+      Some(Event::Key {
+        key: Key::Glyph('q' | 'Q'),
+        ..
+      })
+    );
+    if quit && !(self.timeline.is_empty() && self.queued.is_empty()) {
+      return false;
+    }
+
+    if let Some(entry) = self.timeline.current() {
+      let now = self.clock.now();
+      let ended_by_input = entry.input_ends && pressed;
+      // A wait that input can end is waiting *for* input, so there's nothing
+      // to buffer.
+      let buffer = !entry.input_ends;
+      if ended_by_input || self.timeline.is_due(now) {
+        self.timeline.advance(now);
+        if ended_by_input {
+          // The key that ended the wait shouldn't also do something later.
+          self.queued.clear();
+        }
+        if !self.timeline.is_empty() {
+          if buffer {
+            self.enqueue(event);
+          }
           return true;
         }
       } else {
-        wait.wait_for -= 1;
+        if buffer {
+          self.enqueue(event);
+        }
         return true;
       }
     }
 
+    // Input buffered during a wait goes first, in the order it came in, once
+    // the game is ready for input again.
+    match self.state {
+      State::Standby => {
+        if let Some(queued) = self.queued.pop_front() {
+          self.queue_behind(event);
+          event = Some(queued);
+        }
+      }
+      // Checking a flip ignores input, so keep it for afterwards.
+      State::FlipCheck => self.enqueue(event),
+      _ => {}
+    }
+
     match (self.state, event) {
+      (State::OfferResume, Some(Event::Key { key, .. })) => match key {
+        Key::Glyph('q' | 'Q') => return false,
+        Key::Glyph('y' | 'Y') | Key::Enter => self.accept_resume(true),
+        Key::Glyph('n' | 'N') | Key::Esc => self.accept_resume(false),
+        _ => {}
+      },
+
       (State::NewGame, _) => {
+        // Input from the end of the last round doesn't carry over.
+        self.queued.clear();
         self.generate_board();
+        self.highlight = None;
+        self.analyze();
         self.state = State::Standby;
       }
 
       (State::Standby, Some(Event::Key { key, .. })) => match key {
         Key::Glyph('q' | 'Q') => return false,
         Key::Left => {
-          if self.selected_card % stride == 0 {
+          if self.selected_card.is_multiple_of(stride) {
             self.selected_card += stride - 1;
           } else {
             self.selected_card -= 1;
@@ -326,7 +1010,7 @@ impl Game {
         }
         Key::Right => {
           self.selected_card += 1;
-          if self.selected_card % stride == 0 {
+          if self.selected_card.is_multiple_of(stride) {
             self.selected_card -= stride;
           }
         }
@@ -340,58 +1024,98 @@ impl Game {
         }
         Key::Down => {
           self.selected_card += stride;
-          if self.selected_card > self.cards.len() {
+          if self.selected_card >= self.cards.len() {
             self.selected_card %= stride;
           }
         }
+        // In debug mode, \ always does a slow flip.
         Key::Enter | Key::Glyph('\\')
           if key == Key::Enter || self.options.enable_debugging =>
         {
-          if !self.cards[self.selected_card].flipped {
-            // If only one card remains to be flipped, make this a slow flip
-            // 10% of the time.
-            //
-            // In debug mode, \ will do this too..
-            let remaining = self.cards.iter().filter(|c| c.value > 1).count();
-            let slow = (remaining == 1 && rand::thread_rng().gen_bool(0.1))
-              || key != Key::Enter;
-
-            self.state = State::FlipCheck;
-            self.flip_selected(true, slow);
-          }
+          self.flip(key != Key::Enter)
         }
         Key::Glyph(k @ '0'..='9') => {
           let index = k as u8 - b'0';
           self.cards[self.selected_card].memo ^= 1 << index;
         }
+        Key::Glyph('h' | 'H') => {
+          self.heatmap ^= true;
+          self.analyze();
+        }
+        Key::Glyph('b' | 'B') => self.state = State::ConfirmQuit,
+        Key::Glyph('s' | 'S') => self.save_now(),
+        Key::Glyph('l' | 'L') => self.load_now(),
+        Key::Glyph('t' | 'T') => self.show_stats(),
+        Key::Glyph('r' | 'R') => self.reload_theme(),
+        Key::Glyph('o' | 'O') => self.open_settings(),
         Key::PageUp if self.options.enable_debugging => {
           self.state = State::GameOver {
             new_level: self.level + 1,
-            win: true,
+            outcome: Outcome::Win,
           };
         }
         Key::PageDown if self.options.enable_debugging => {
           self.state = State::GameOver {
             new_level: self.level - 1,
-            win: false,
+            outcome: Outcome::Loss,
           };
         }
         _ => {}
       },
 
+      (
+        State::Standby,
+        Some(Event::Mouse {
+          button,
+          cell,
+          action,
+          ..
+        }),
+      ) => {
+        let target = self.frame.as_ref().and_then(|f| f.target_at(cell));
+        match (target, action, button) {
+          (
+            Some(gfx::Target::Card(i)),
+            MouseAction::Move | MouseAction::Drag,
+            _,
+          ) => self.selected_card = i,
+          (Some(gfx::Target::Card(i)), MouseAction::Press, Some(0)) => {
+            self.selected_card = i;
+            self.flip(false);
+          }
+          (Some(gfx::Target::Card(i)), MouseAction::Press, Some(1))
+          | (Some(gfx::Target::Card(i)), MouseAction::ScrollUp, _) => {
+            self.selected_card = i;
+            self.cycle_memo(true);
+          }
+          (Some(gfx::Target::Card(i)), MouseAction::ScrollDown, _) => {
+            self.selected_card = i;
+            self.cycle_memo(false);
+          }
+          (Some(gfx::Target::Hint(line)), MouseAction::Press, Some(0)) => {
+            if self.highlight == Some(line) {
+              self.highlight = None;
+            } else {
+              self.highlight = Some(line);
+            }
+          }
+          _ => {}
+        }
+      }
+
+      (State::ConfirmQuit, Some(Event::Key { key, .. })) => match key {
+        Key::Glyph('q' | 'Q') => return false,
+        Key::Glyph('y' | 'Y') | Key::Enter => self.end_round(Outcome::Quit),
+        Key::Glyph('n' | 'N' | 'b' | 'B') | Key::Esc => {
+          self.state = State::Standby
+        }
+        _ => {}
+      },
+
       (State::FlipCheck, _) => {
         let card = &mut self.cards[self.selected_card];
         if card.value == 0 {
-          let flipped = self.cards.iter().filter(|x| x.flipped).count();
-          self.state = State::GameOver {
-            new_level: (flipped as u32 - 1).min(self.level),
-            win: false,
-          };
-          self.flip_all(true);
-          self.waits.push(Wait {
-            wait_for: 30 * 5,
-            input_ends_wait: true,
-          });
+          self.end_round(Outcome::Loss);
           return true;
         }
 
@@ -406,26 +1130,22 @@ impl Game {
           .iter()
           .any(|card| card.value > 1 && !card.flipped)
         {
-          self.state = State::GameOver {
-            new_level: self.level + 1,
-            win: true,
-          };
-          self.flip_all(true);
-          self.waits.push(Wait {
-            wait_for: 30 * 5,
-            input_ends_wait: true,
-          });
+          self.end_round(Outcome::Win);
           return true;
         }
 
+        self.analyze();
         self.state = State::Standby;
       }
 
-      (State::GameOver { new_level, win }, _) => {
-        if win {
+      (State::GameOver { new_level, outcome }, _) => {
+        if outcome != Outcome::Loss {
           self.score += self.round_score;
         }
         self.level = new_level.clamp(1, MAX_LEVEL as u32);
+        self.seed = StdRng::seed_from_u64(self.seed).gen();
+        // The old odds are meaningless once the cards are turned back over.
+        self.analysis = None;
         self.flip_all(false);
         self.state = State::NewGame;
       }
@@ -435,3 +1155,20 @@ impl Game {
     true
   }
 }
+
+/// Returns the animation of the card at `idx` turning over: it narrows at
+/// `step` per stage, and then widens again at the usual speed.
+fn flip_track(idx: usize, step: Duration) -> Track {
+  let half = gfx::FLIP_STAGES as u32 / 2;
+  let narrow = Tween::new(0, half + 1, step * (half + 1));
+  let widen = Tween::new(
+    half,
+    gfx::FLIP_STAGES as u32,
+    FLIP_STEP * (gfx::FLIP_STAGES as u32 - half),
+  );
+  Track::new(
+    Entity::Card(idx),
+    Effect::Flip,
+    Sequence::new([narrow, widen]),
+  )
+}
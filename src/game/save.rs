@@ -0,0 +1,378 @@
+//! Saving and resuming games.
+//!
+//! A game is saved as JSON in the user's data directory. Every save records
+//! the version of the format it was written in; saves from other versions are
+//! refused rather than misread, so `VERSION` must be bumped whenever the
+//! format changes.
+
+use std::fs;
+use std::io;
+use std::panic;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::game::Card;
+use crate::game::Game;
+use crate::game::Generator;
+use crate::game::Hint;
+use crate::game::State;
+use crate::game::MAX_LEVEL;
+
+/// The current version of the save format.
+const VERSION: u32 = 1;
+
+/// A snapshot of everything needed to pick a game back up.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Save {
+  version: u32,
+
+  board_dims: (u32, u32),
+  max_card_value: u8,
+  generator: Generator,
+
+  level: u32,
+  score: u64,
+  round_score: u64,
+  streak: u32,
+  seed: u64,
+
+  cards: Vec<Card>,
+  col_hints: Vec<Hint>,
+  row_hints: Vec<Hint>,
+
+  selected_card: usize,
+  state: State,
+  heatmap: bool,
+}
+
+impl Save {
+  /// Returns the path games are saved to, if the platform has a data
+  /// directory.
+  pub fn path() -> Option<PathBuf> {
+    Some(data_dir()?.join("save.json"))
+  }
+
+  /// Reads the saved game, if there is one.
+  pub fn read() -> io::Result<Option<Save>> {
+    match Self::path() {
+      Some(path) => Self::read_from(&path),
+      None => Ok(None),
+    }
+  }
+
+  /// Reads a saved game from `path`, if there is one.
+  fn read_from(path: &Path) -> io::Result<Option<Save>> {
+    let json = match fs::read_to_string(path) {
+      Ok(json) => json,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+      Err(e) => return Err(e),
+    };
+
+    // Check the version before anything else, since the rest of the format
+    // may have changed.
+    #[derive(Deserialize)]
+    struct Version {
+      version: u32,
+    }
+    let Version { version } = serde_json::from_str(&json)?;
+    if version != VERSION {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("saved game has unsupported version {version}"),
+      ));
+    }
+
+    let save: Save = serde_json::from_str(&json)?;
+    if !save.is_valid() {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "saved game is corrupt",
+      ));
+    }
+    Ok(Some(save))
+  }
+
+  /// Writes this game to disk, replacing any previous save.
+  pub fn write(&self) -> io::Result<()> {
+    self.write_to(&data_file("save.json")?)
+  }
+
+  /// Writes this game to `path`, replacing any previous save there.
+  fn write_to(&self, path: &Path) -> io::Result<()> {
+    // Write to the side and then move it into place, so that a crash halfway
+    // through can't destroy the old save.
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, serde_json::to_string(self)?)?;
+    fs::rename(temp, path)
+  }
+
+  /// Returns the level the saved game is at.
+  pub fn level(&self) -> u32 {
+    self.level
+  }
+
+  /// Returns the total coins of the saved game.
+  pub fn score(&self) -> u64 {
+    self.score
+  }
+
+  /// Checks that the save describes a board the game can actually display.
+  fn is_valid(&self) -> bool {
+    let (width, height) = self.board_dims;
+    (3..=16).contains(&width)
+      && (3..=16).contains(&height)
+      && (3..=9).contains(&self.max_card_value)
+      && (1..=MAX_LEVEL as u32).contains(&self.level)
+      && self.cards.len() == (width * height) as usize
+      && self.col_hints.len() == width as usize
+      && self.row_hints.len() == height as usize
+      && self.selected_card < self.cards.len()
+      && self.cards.iter().all(|c| c.value <= self.max_card_value)
+      && !matches!(self.state, State::OfferResume)
+  }
+}
+
+/// Returns the directory the game keeps its files in, if the platform has a
+/// data directory.
+pub(super) fn data_dir() -> Option<PathBuf> {
+  Some(dirs::data_dir()?.join("voltorb"))
+}
+
+/// Returns the path of the file `name` in the game's data directory, creating
+/// the directory if necessary.
+pub(super) fn data_file(name: &str) -> io::Result<PathBuf> {
+  let Some(dir) = data_dir() else {
+    return Err(io::Error::new(
+      io::ErrorKind::NotFound,
+      "no data directory to write to",
+    ));
+  };
+  fs::create_dir_all(&dir)?;
+  Ok(dir.join(name))
+}
+
+impl Game {
+  /// Takes a snapshot of this game.
+  ///
+  /// If the player is still being asked whether to resume a saved game, that
+  /// game is returned instead, so that it isn't lost if they quit.
+  pub fn save(&self) -> Save {
+    if let Some(save) = &self.resume {
+      return save.clone();
+    }
+
+    Save {
+      version: VERSION,
+      board_dims: self.options.board_dims,
+      max_card_value: self.options.max_card_value,
+      generator: self.options.generator,
+      level: self.level,
+      score: self.score,
+      round_score: self.round_score,
+      streak: self.streak,
+      seed: self.seed,
+      cards: self.cards.clone(),
+      col_hints: self.col_hints.clone(),
+      row_hints: self.row_hints.clone(),
+      selected_card: self.selected_card,
+      state: self.state,
+      heatmap: self.heatmap,
+    }
+  }
+
+  /// Offers to resume `save` the next time the game is started up.
+  pub fn offer_resume(&mut self, save: Save) {
+    self.resume = Some(save);
+    self.state = State::OfferResume;
+  }
+
+  /// Replaces this game with a saved one.
+  ///
+  /// The board options are taken from the save. Later rounds are dealt from
+  /// the seed of the saved round, just as they would have been had the game
+  /// not been interrupted.
+  fn restore(&mut self, save: Save) {
+    self.options.board_dims = save.board_dims;
+    self.options.max_card_value = save.max_card_value;
+    self.options.generator = save.generator;
+
+    self.level = save.level;
+    self.score = save.score;
+    self.round_score = save.round_score;
+    self.streak = save.streak;
+    self.seed = save.seed;
+    self.rng = StdRng::seed_from_u64(save.seed);
+
+    self.cards = save.cards;
+    self.col_hints = save.col_hints;
+    self.row_hints = save.row_hints;
+
+    self.selected_card = save.selected_card;
+    self.state = save.state;
+    self.heatmap = save.heatmap;
+    self.resume = None;
+
+    // Animations aren't saved; whatever was in flight has finished.
+    self.timeline.clear();
+    self.queued.clear();
+    self.analyze();
+  }
+
+  /// Saves this game, reporting how it went to the player.
+  pub(super) fn save_now(&mut self) {
+    self.notice = Some(match self.save().write() {
+      Ok(()) => "Game saved.".to_string(),
+      Err(e) => format!("Could not save: {e}"),
+    });
+  }
+
+  /// Loads the saved game, reporting how it went to the player.
+  pub(super) fn load_now(&mut self) {
+    self.notice = Some(match Save::read() {
+      Ok(Some(save)) => {
+        self.restore(save);
+        "Game loaded.".to_string()
+      }
+      Ok(None) => "No saved game.".to_string(),
+      Err(e) => format!("Could not load: {e}"),
+    });
+  }
+
+  /// Resumes the game offered by `offer_resume()`, or starts a new one.
+  pub(super) fn accept_resume(&mut self, accept: bool) {
+    match self.resume.take() {
+      Some(save) if accept => self.restore(save),
+      _ => self.state = State::NewGame,
+    }
+  }
+}
+
+/// The most recent snapshot of a game, so that it can be saved even if the
+/// process panics.
+#[derive(Clone, Default)]
+pub struct Autosave(Arc<Mutex<Option<Save>>>);
+
+impl Autosave {
+  /// Records the current state of `game`.
+  pub fn update(&self, game: &Game) {
+    if let Ok(mut snapshot) = self.0.lock() {
+      *snapshot = Some(game.save());
+    }
+  }
+
+  /// Installs a panic handler that saves the last snapshot before anything
+  /// else happens.
+  pub fn install_panic_hook(&self) {
+    let hook = panic::take_hook();
+    let copy = self.clone();
+    panic::set_hook(Box::new(move |info| {
+      // Don't block: the panic may have happened while holding the lock.
+      if let Ok(snapshot) = copy.0.try_lock() {
+        if let Some(save) = &*snapshot {
+          let _ = save.write();
+        }
+      }
+      hook(info);
+    }));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::tests::options;
+
+  /// Returns a game partway through a round.
+  fn game(seed: u64) -> Game {
+    let mut game = Game::new(options((5, 4), seed));
+    game.interact(None);
+    game.level = 3;
+    game.score = 120;
+    game.round_score = 6;
+    game.streak = 2;
+    game.cards[7].flipped = true;
+    game.cards[3].memo = 0b101;
+    game.selected_card = 7;
+    game
+  }
+
+  /// A directory for one test to save to, removed when it's dropped.
+  struct TempDir(PathBuf);
+
+  impl TempDir {
+    fn new(test: &str) -> Self {
+      let dir = std::env::temp_dir()
+        .join(format!("voltorb-test-{}-{test}", std::process::id()));
+      fs::create_dir_all(&dir).unwrap();
+      Self(dir)
+    }
+  }
+
+  impl Drop for TempDir {
+    fn drop(&mut self) {
+      let _ = fs::remove_dir_all(&self.0);
+    }
+  }
+
+  #[test]
+  fn round_trip() {
+    let dir = TempDir::new("round_trip");
+    let path = dir.0.join("save.json");
+    let before = game(1);
+    before.save().write_to(&path).unwrap();
+    let save = Save::read_from(&path).unwrap().unwrap();
+
+    let mut after = Game::new(options((3, 3), 2));
+    after.restore(save);
+    assert_eq!(after.options.board_dims, (5, 4));
+    assert_eq!(
+      (after.level, after.score, after.round_score, after.streak),
+      (3, 120, 6, 2),
+    );
+    assert_eq!(after.seed, before.seed);
+    assert_eq!(after.selected_card, 7);
+    assert!(matches!(after.state, State::Standby));
+    for (a, b) in Iterator::zip(after.cards.iter(), &before.cards) {
+      assert_eq!((a.value, a.flipped, a.memo), (b.value, b.flipped, b.memo));
+    }
+    for (a, b) in Iterator::zip(after.row_hints.iter(), &before.row_hints)
+      .chain(Iterator::zip(after.col_hints.iter(), &before.col_hints))
+    {
+      assert_eq!((a.sum, a.voltorbs), (b.sum, b.voltorbs));
+    }
+  }
+
+  #[test]
+  fn invalid_saves() {
+    assert!(game(1).save().is_valid());
+    let cases: [fn(&mut Save); 5] = [
+      |s| s.board_dims = (2, 4),
+      |s| s.board_dims = (5, 17),
+      |s| s.board_dims = (4, 5),
+      |s| s.cards.truncate(19),
+      |s| s.level = 0,
+    ];
+    for (i, corrupt) in cases.iter().enumerate() {
+      let mut save = game(1).save();
+      corrupt(&mut save);
+      assert!(!save.is_valid(), "case {i}");
+    }
+
+    // Saves from other versions aren't even looked at.
+    let dir = TempDir::new("invalid_saves");
+    let path = dir.0.join("save.json");
+    let mut save = game(1).save();
+    save.version = VERSION + 1;
+    save.write_to(&path).unwrap();
+    let error = Save::read_from(&path).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("unsupported version"), "{error}");
+  }
+}
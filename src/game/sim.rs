@@ -0,0 +1,335 @@
+//! Headless play, for measuring how fair boards are.
+//!
+//! The simulator drives a [`Game`] through `Game::interact()` exactly like the
+//! TUI does, minus the terminal: a bot picks a card, presses Enter, and lets
+//! time pass until the game wants input again. Nobody is watching, so time is
+//! faked and passes as quickly as the game can take it.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use enumflags2::BitFlags;
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::SeedableRng;
+
+use crate::game::parse_name;
+use crate::game::solver;
+use crate::game::Game;
+use crate::game::ManualClock;
+use crate::game::Options;
+use crate::game::Outcome;
+use crate::game::State;
+use crate::game::MAX_LEVEL;
+use crate::term::Event;
+use crate::term::Key;
+
+/// A way of picking which card to flip next.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Strategy {
+  /// Flips any face-down card, uniformly at random.
+  Random,
+  /// Flips the card least likely to be a Voltorb, skipping cards that provably
+  /// can't be multipliers.
+  GreedySafest,
+}
+
+impl Strategy {
+  const ALL: [(Strategy, &'static str); 2] = [
+    (Self::Random, "random"),
+    (Self::GreedySafest, "greedy-safest"),
+  ];
+
+  /// Returns this strategy's name on the command line.
+  pub fn name(self) -> &'static str {
+    Self::ALL.iter().find(|(s, _)| *s == self).unwrap().1
+  }
+
+  /// Picks the index of the next card to flip.
+  fn pick(self, game: &Game, rng: &mut StdRng) -> usize {
+    let face_down = (0..game.cards.len()).filter(|&i| !game.cards[i].flipped);
+
+    let analysis = match self {
+      Self::Random => None,
+      Self::GreedySafest => solver::solve(
+        &game.options,
+        &game.cards,
+        &game.row_hints,
+        &game.col_hints,
+      ),
+    };
+    let Some(analysis) = analysis else {
+      return face_down.choose(rng).unwrap();
+    };
+
+    // Cards that can only be a 1 or a Voltorb are never worth the risk. Of the
+    // rest, prefer the safest, and then the most likely to pay out.
+    let odds = &analysis.odds;
+    face_down
+      .filter(|&i| !odds[i].is_useless())
+      .min_by(|&a, &b| {
+        f64::total_cmp(&odds[a].voltorb(), &odds[b].voltorb())
+          .then(f64::total_cmp(&odds[b].multiplier(), &odds[a].multiplier()))
+      })
+      .unwrap()
+  }
+}
+
+impl FromStr for Strategy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    parse_name(&Self::ALL, s)
+  }
+}
+
+/// The outcome of one simulated round.
+#[derive(Copy, Clone, Debug)]
+struct Round {
+  /// The level the round was played at.
+  level: u32,
+  /// Whether every multiplier was found.
+  win: bool,
+  /// The coins added to the total by this round.
+  coins: u64,
+}
+
+/// The results of a simulation, ready to be printed.
+pub struct Report {
+  options: Options,
+  strategy: Strategy,
+  seed: u64,
+  rounds: Vec<Round>,
+  /// The level the game ended up at after the last round.
+  final_level: u32,
+}
+
+/// Plays `rounds` rounds of a single game with `strategy`, and reports on how
+/// it went.
+pub fn simulate(options: Options, strategy: Strategy, rounds: usize) -> Report {
+  let clock = ManualClock::default();
+  let mut game = Game::with_clock(
+    Options {
+      record_rounds: false,
+      enable_debugging: false,
+      ..options
+    },
+    Box::new(clock.clone()),
+  );
+  // Long enough to get through any single wait in one go.
+  let tick = Duration::from_secs(1);
+
+  // The bot's own choices are seeded too, so that a whole simulation can be
+  // reproduced with `--seed`.
+  let seed = game.seed;
+  let mut rng = StdRng::seed_from_u64(seed);
+  let enter = Event::Key {
+    key: Key::Enter,
+    mods: BitFlags::empty(),
+  };
+
+  let mut results = Vec::with_capacity(rounds);
+  while results.len() < rounds {
+    let (level, score) = (game.level, game.score);
+    let win = loop {
+      match game.state {
+        State::Standby if game.timeline.is_empty() => {
+          game.selected_card = strategy.pick(&game, &mut rng);
+          game.interact(Some(enter));
+        }
+        State::GameOver { outcome, .. } => break outcome == Outcome::Win,
+        _ => {
+          clock.advance(tick);
+          game.interact(None);
+        }
+      }
+    };
+
+    // Run out the clock on the game over screen, which is where the level and
+    // score get updated.
+    while !matches!(game.state, State::NewGame) {
+      clock.advance(tick);
+      game.interact(None);
+    }
+    results.push(Round {
+      level,
+      win,
+      coins: game.score - score,
+    });
+  }
+
+  Report {
+    strategy,
+    seed,
+    rounds: results,
+    final_level: game.level,
+    options: game.options,
+  }
+}
+
+impl fmt::Display for Report {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let (width, height) = self.options.board_dims;
+    writeln!(
+      f,
+      "{} rounds on {width}x{height} {} boards up to {}, playing {}, seed {}",
+      self.rounds.len(),
+      self.options.generator.name(),
+      self.options.max_card_value,
+      self.strategy.name(),
+      self.seed,
+    )?;
+    writeln!(f)?;
+
+    // The level a round moved to is the level the next round was played at.
+    let next_levels = self
+      .rounds
+      .iter()
+      .skip(1)
+      .map(|r| r.level)
+      .chain([self.final_level]);
+
+    let mut levels = [Stats::default(); MAX_LEVEL];
+    let mut total = Stats::default();
+    for (round, next) in Iterator::zip(self.rounds.iter(), next_levels) {
+      levels[round.level as usize - 1].add(round, next);
+      total.add(round, next);
+    }
+
+    writeln!(
+      f,
+      "{:>5}  {:>6}  {:>6}  {:>9}  {:>6}  {:>6}  {:>6}",
+      "level", "rounds", "win%", "coins", "up", "stay", "down",
+    )?;
+    for (i, stats) in levels.iter().enumerate() {
+      if stats.rounds > 0 {
+        writeln!(f, "{:>5}  {stats}", i + 1)?;
+      }
+    }
+    writeln!(f, "{:>5}  {total}", "all")?;
+    writeln!(f)?;
+
+    // Show how the level drifts over the course of the game by averaging it
+    // over a handful of equal stretches of rounds.
+    writeln!(f, "average level over time:")?;
+    let stretch = self.rounds.len().div_ceil(10).max(1);
+    for (i, rounds) in self.rounds.chunks(stretch).enumerate() {
+      let sum = rounds.iter().map(|r| r.level as f64).sum::<f64>();
+      let (start, end) = (i * stretch + 1, i * stretch + rounds.len());
+      writeln!(
+        f,
+        "  rounds {:>15}  {:.2}",
+        format!("{start}-{end}"),
+        sum / rounds.len() as f64,
+      )?;
+    }
+    Ok(())
+  }
+}
+
+/// Aggregate results for a set of rounds.
+#[derive(Copy, Clone, Default)]
+struct Stats {
+  rounds: u64,
+  wins: u64,
+  coins: u64,
+  up: u64,
+  down: u64,
+}
+
+impl Stats {
+  fn add(&mut self, round: &Round, next_level: u32) {
+    self.rounds += 1;
+    self.wins += round.win as u64;
+    self.coins += round.coins;
+    self.up += (next_level > round.level) as u64;
+    self.down += (next_level < round.level) as u64;
+  }
+}
+
+impl fmt::Display for Stats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let n = self.rounds as f64;
+    let percent = |x: u64| format!("{:.1}%", x as f64 / n * 100.0);
+    write!(
+      f,
+      "{:>6}  {:>6}  {:>9.1}  {:>6}  {:>6}  {:>6}",
+      self.rounds,
+      percent(self.wins),
+      self.coins as f64 / n,
+      percent(self.up),
+      percent(self.rounds - self.up - self.down),
+      percent(self.down),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::Generator;
+  use crate::game::HintSide;
+  use crate::game::Theme;
+
+  fn options() -> Options {
+    Options {
+      board_dims: (5, 5),
+      max_card_value: 3,
+      seed: Some(1),
+      generator: Generator::Formula,
+      record_rounds: false,
+      theme: Theme::Builtin("default"),
+      card_size: None,
+      layout: None,
+      hint_side: HintSide::BottomRight,
+      enable_debugging: false,
+    }
+  }
+
+  #[test]
+  fn every_strategy_finishes() {
+    for (strategy, _) in Strategy::ALL {
+      let report = simulate(options(), strategy, 5);
+      assert_eq!(report.rounds.len(), 5, "{strategy:?}");
+      assert!((1..=MAX_LEVEL as u32).contains(&report.final_level));
+    }
+  }
+
+  #[test]
+  fn strategy_names() {
+    for (strategy, name) in Strategy::ALL {
+      assert_eq!(name.parse::<Strategy>(), Ok(strategy));
+      assert_eq!(strategy.name(), name);
+    }
+    assert_eq!(
+      "safest".parse::<Strategy>(),
+      Err("expected one of: random, greedy-safest".to_string()),
+    );
+  }
+
+  #[test]
+  fn report() {
+    // The same seed plays out the same way.
+    let report = simulate(options(), Strategy::GreedySafest, 12).to_string();
+    assert_eq!(
+      report,
+      simulate(options(), Strategy::GreedySafest, 12).to_string(),
+    );
+
+    let lines = report.lines().collect::<Vec<_>>();
+    assert_eq!(
+      lines[0],
+      "12 rounds on 5x5 formula boards up to 3, playing greedy-safest, seed 1",
+    );
+    assert!(lines[2].starts_with("level  rounds"), "{report}");
+    let all = lines.iter().find(|l| l.starts_with("  all")).unwrap();
+    assert!(all.starts_with("  all      12  "), "{report}");
+
+    // Twelve rounds are averaged in stretches of two.
+    let stretches =
+      lines.iter().skip_while(|l| !l.starts_with("average level"));
+    assert_eq!(stretches.clone().count(), 7, "{report}");
+    assert!(stretches.last().unwrap().contains("11-12"), "{report}");
+  }
+}
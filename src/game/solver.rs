@@ -0,0 +1,817 @@
+//! A probability solver for Voltorb Flip boards.
+//!
+//! The solver only looks at the information a player can see: the row and
+//! column hints, and the values of cards that have already been flipped. It
+//! considers every assignment of values to the face-down cards that agrees with
+//! that information, treating each one as equally likely, and reports the odds
+//! for every card.
+//!
+//! Boards are built one line at a time. The state carried between lines is the
+//! running sum and Voltorb count of each crossing line, and partial boards that
+//! share a state are merged, so the solver counts boards without visiting them
+//! one by one. Counting starts from the most constrained lines, and for each
+//! state records how many ways there are to finish the board from it.
+//!
+//! On large, fresh boards with high card values the number of states can still
+//! explode. When that happens, the solver stops counting and estimates the odds
+//! with belief propagation instead: each line is treated as a constraint on its
+//! cards, and rows and columns trade messages about them until they agree.
+
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::hash::Hasher;
+
+use crate::game::Card;
+use crate::game::Hint;
+use crate::game::Options;
+
+/// The odds for a single card.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Odds {
+  /// The probability of each value for this card; index zero is a Voltorb.
+  pub values: [f64; 10],
+  /// Bitset of values this card could possibly take. This is exact when the
+  /// analysis is, and a sound over-approximation otherwise.
+  possible: u16,
+}
+
+impl Odds {
+  /// Returns the probability that this card is a Voltorb.
+  pub fn voltorb(&self) -> f64 {
+    self.values[0]
+  }
+
+  /// Returns the probability that this card is a multiplier of two or more.
+  pub fn multiplier(&self) -> f64 {
+    self.values[2..].iter().sum()
+  }
+
+  /// Returns the expected value of this card, counting Voltorbs as zero.
+  pub fn expected(&self) -> f64 {
+    self
+      .values
+      .iter()
+      .enumerate()
+      .map(|(v, p)| v as f64 * p)
+      .sum()
+  }
+
+  /// Returns whether this card is provably not a Voltorb.
+  pub fn is_safe(&self) -> bool {
+    self.possible & 1 == 0
+  }
+
+  /// Returns whether this card is provably a Voltorb.
+  pub fn is_voltorb(&self) -> bool {
+    self.possible == 1
+  }
+
+  /// Returns whether this card is provably not a multiplier, i.e., whether
+  /// flipping it can never help finish the round.
+  pub fn is_useless(&self) -> bool {
+    self.possible & !0b11 == 0
+  }
+}
+
+/// The result of solving a board.
+#[derive(Clone, Debug)]
+pub struct Analysis {
+  /// The odds for each card, in the same order as `Game::cards`.
+  ///
+  /// Flipped cards have their value with certainty.
+  pub odds: Vec<Odds>,
+  /// Whether the odds were computed exactly, rather than estimated.
+  pub exact: bool,
+}
+
+/// The number of partial line fillings the solver will visit while counting
+/// before it gives up on an exact answer.
+const WORK_LIMIT: usize = 1 << 21;
+
+/// The most rounds of message passing the solver will run when it can't count
+/// exactly.
+const MAX_ROUNDS: usize = 200;
+
+/// Computes the odds for every card on a board.
+///
+/// Only the value of flipped cards is inspected. Returns `None` if no board
+/// is consistent with the hints, which can only happen if they were not
+/// produced by `Game::generate_board`.
+///
+/// The odds are exact unless counting the boards would take too long, in which
+/// case they are only estimates, and `Analysis::exact` is false. Estimated odds
+/// never rule out a value that some consistent board gives a card, but they can
+/// be some way off the true odds otherwise.
+pub fn solve(
+  options: &Options,
+  cards: &[Card],
+  row_hints: &[Hint],
+  col_hints: &[Hint],
+) -> Option<Analysis> {
+  let (width, height) = options.board_dims;
+  let (width, height) = (width as usize, height as usize);
+  let known = cards
+    .iter()
+    .map(|c| c.flipped.then_some(c.value))
+    .collect::<Vec<_>>();
+
+  // The state carried between lines has one entry per crossing line, so sweep
+  // along whichever axis has fewer of them.
+  let transpose = width > height;
+  let (width, height, known, lines, crossing) = if transpose {
+    let known = (0..cards.len())
+      .map(|i| known[(i % height) * width + i / height])
+      .collect();
+    (height, width, known, col_hints, row_hints)
+  } else {
+    (width, height, known, row_hints, col_hints)
+  };
+
+  let mut grid = Grid {
+    width,
+    height,
+    candidates: Vec::new(),
+    lines,
+    crossing,
+    max_card: options.max_card_value,
+  };
+  grid.candidates = known
+    .iter()
+    .enumerate()
+    .map(|(i, &known)| grid.candidates(i % width, known))
+    .collect();
+
+  let mut analysis = grid.solve()?;
+  if transpose {
+    analysis.odds = (0..cards.len())
+      .map(|i| analysis.odds[(i % height) * width + i / height])
+      .collect();
+  }
+  Some(analysis)
+}
+
+/// A board, normalized so that it is swept row by row.
+struct Grid<'a> {
+  width: usize,
+  height: usize,
+  /// Bitsets of the values each card might take, ignoring interactions
+  /// between lines.
+  candidates: Vec<u16>,
+  /// Hints for the lines being swept.
+  lines: &'a [Hint],
+  /// Hints for the lines crossing the swept lines.
+  crossing: &'a [Hint],
+  max_card: u8,
+}
+
+/// The largest number of crossing lines the solver can track; this is the
+/// largest board dimension `Options` allows.
+const MAX_CROSSING: usize = 16;
+
+/// The values along one line of the board; only the first `Grid::width` are
+/// meaningful.
+type Line = [u8; MAX_CROSSING];
+
+/// The running sum and Voltorb count of every crossing line, packed into one
+/// `u16` each.
+type State = [u16; MAX_CROSSING];
+
+/// A Voltorb count in a packed `State` entry.
+const VOLTORB: u16 = 1 << 11;
+
+/// Packs a single card value into a `State` entry.
+fn pack(v: u8) -> u16 {
+  if v == 0 {
+    VOLTORB
+  } else {
+    v as u16
+  }
+}
+
+/// Counts of the ways to finish a board from a given state.
+type Completions = HashMap<State, f64, BuildHasherDefault<StateHasher>>;
+
+/// A fast hasher for `State`s; the default hasher spends most of the solver's
+/// time hashing otherwise.
+#[derive(Default)]
+struct StateHasher(u64);
+
+impl Hasher for StateHasher {
+  fn write(&mut self, bytes: &[u8]) {
+    for chunk in bytes.chunks(8) {
+      let mut word = [0; 8];
+      word[..chunk.len()].copy_from_slice(chunk);
+      self.0 = (self.0.rotate_left(5) ^ u64::from_le_bytes(word))
+        .wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+  }
+
+  fn finish(&self) -> u64 {
+    self.0
+  }
+}
+
+/// Relative weights for each value of a single card.
+type Weights = [f64; 10];
+
+impl Grid<'_> {
+  fn solve(&self) -> Option<Analysis> {
+    let fillings = (0..self.height)
+      .map(|y| self.messages(self.lines[y], &self.priors(y)).0)
+      .collect::<Vec<_>>();
+
+    // Count from the most constrained lines first: those go at the end of
+    // `order`, since counting runs backwards.
+    let mut order = (0..self.height).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| fillings[b].total_cmp(&fillings[a]));
+
+    // `completions[k]` holds the ways to finish the board from a state reached
+    // after the first `k` lines of `order`.
+    let mut budget = WORK_LIMIT;
+    let mut completions = vec![Completions::default()];
+    completions[0].insert(self.target(), 1.0);
+    for (k, &y) in order.iter().enumerate().rev() {
+      let Some(prev) = self.count(y, k, &completions[0], &mut budget) else {
+        return self.propagate();
+      };
+      completions.insert(0, prev);
+    }
+
+    self.odds(&order, &completions)
+  }
+
+  /// Computes the completion counts for states reached after the first `k`
+  /// lines of the order, given the counts after line `y`, the `k`th.
+  ///
+  /// Returns `None` if this would exceed `budget`.
+  fn count(
+    &self,
+    y: usize,
+    k: usize,
+    next: &Completions,
+    budget: &mut usize,
+  ) -> Option<Completions> {
+    let mut prev = Completions::default();
+    for (state, &count) in next {
+      let complete = self.predecessors(y, k, state, budget, &mut |_, s| {
+        *prev.entry(s).or_insert(0.0) += count;
+      });
+      if !complete {
+        return None;
+      }
+    }
+    Some(prev)
+  }
+
+  /// Computes exact odds, given completion counts for every line.
+  fn odds(
+    &self,
+    order: &[usize],
+    completions: &[Completions],
+  ) -> Option<Analysis> {
+    let mut odds = vec![Odds::default(); self.width * self.height];
+    let boards = *completions[0].get(&[0; MAX_CROSSING])?;
+
+    // Sweep forwards, counting the ways to reach each state; a filling then
+    // appears in as many boards as there are ways to reach it times the ways
+    // to finish from it. Only states that can finish the board matter, so
+    // work backwards from those, like `count()` does.
+    let mut reach = Completions::default();
+    reach.insert([0; MAX_CROSSING], 1.0);
+    for (k, &y) in order.iter().enumerate() {
+      let mut next = Completions::default();
+      for (state, &w) in &completions[k + 1] {
+        self.predecessors(y, k, state, &mut { usize::MAX }, &mut |line, s| {
+          let Some(&count) = reach.get(&s) else {
+            return;
+          };
+          *next.entry(*state).or_insert(0.0) += count;
+          for (x, &v) in line[..self.width].iter().enumerate() {
+            let odds = &mut odds[y * self.width + x];
+            odds.values[v as usize] += count * w;
+            odds.possible |= 1 << v;
+          }
+        });
+      }
+      reach = next;
+    }
+
+    for odds in &mut odds {
+      for p in &mut odds.values {
+        *p /= boards;
+      }
+    }
+    Some(Analysis { odds, exact: true })
+  }
+
+  /// Estimates the odds with loopy belief propagation.
+  ///
+  /// Every line tells each of its cards how many of the line's fillings give
+  /// it each value, weighing the other cards by what their crossing lines last
+  /// said about them. The odds are wherever these messages settle.
+  ///
+  /// This is not exact, since it ignores the loops formed by rows and columns,
+  /// but a value only ever gets zero weight when no filling of some line can
+  /// accommodate it, so the values it rules out are ruled out for certain.
+  fn propagate(&self) -> Option<Analysis> {
+    let priors = (0..self.height)
+      .flat_map(|y| self.priors(y))
+      .collect::<Vec<_>>();
+    let mut from_lines = priors.clone();
+    let mut from_crossing = priors.clone();
+
+    let rows = (0..self.height)
+      .map(|y| {
+        let cards = (0..self.width).map(|x| y * self.width + x).collect();
+        (self.lines[y], cards)
+      })
+      .collect::<Vec<(Hint, Vec<usize>)>>();
+    let cols = (0..self.width)
+      .map(|x| {
+        let cards = (0..self.height).map(|y| y * self.width + x).collect();
+        (self.crossing[x], cards)
+      })
+      .collect::<Vec<(Hint, Vec<usize>)>>();
+
+    for _ in 0..MAX_ROUNDS {
+      let delta = f64::max(
+        self.pass(&rows, &priors, &from_crossing, &mut from_lines)?,
+        self.pass(&cols, &priors, &from_lines, &mut from_crossing)?,
+      );
+      if delta < 1e-9 {
+        break;
+      }
+    }
+
+    let mut odds = Vec::with_capacity(priors.len());
+    for (i, prior) in priors.iter().enumerate() {
+      let belief = product(&product(prior, &from_lines[i]), &from_crossing[i]);
+      let total = belief.iter().sum::<f64>();
+      if total == 0.0 {
+        return None;
+      }
+
+      let mut card = Odds::default();
+      for (v, &w) in belief.iter().enumerate() {
+        card.values[v] = w / total;
+        if w > 0.0 {
+          card.possible |= 1 << v;
+        }
+      }
+      odds.push(card);
+    }
+    Some(Analysis { odds, exact: false })
+  }
+
+  /// Runs one round of message passing from `lines` to their cards, given the
+  /// messages the cards last got from the lines crossing them.
+  ///
+  /// Returns how far the messages moved, or `None` if some line has no
+  /// fillings left.
+  fn pass(
+    &self,
+    lines: &[(Hint, Vec<usize>)],
+    priors: &[Weights],
+    incoming: &[Weights],
+    outgoing: &mut [Weights],
+  ) -> Option<f64> {
+    let mut delta = 0f64;
+    for (hint, cards) in lines {
+      let weights = cards
+        .iter()
+        .map(|&i| product(&priors[i], &incoming[i]))
+        .collect::<Vec<_>>();
+      let (total, messages) = self.messages(*hint, &weights);
+      if total == 0.0 {
+        return None;
+      }
+      for (&i, new) in Iterator::zip(cards.iter(), &messages) {
+        delta = delta.max(damp(&mut outgoing[i], new));
+      }
+    }
+    Some(delta)
+  }
+
+  /// Returns the weights of the cards on line `y` before any hints are taken
+  /// into account: one for each candidate value, and zero otherwise.
+  fn priors(&self, y: usize) -> Vec<Weights> {
+    self.candidates[y * self.width..][..self.width]
+      .iter()
+      .map(|&candidates| {
+        let mut weights = [0.0; 10];
+        for (v, w) in weights.iter_mut().enumerate() {
+          if candidates & 1 << v != 0 {
+            *w = 1.0;
+          }
+        }
+        weights
+      })
+      .collect()
+  }
+
+  /// Computes the messages a line with the given hint sends to its cards.
+  ///
+  /// A filling of the line weighs the product of its cards' `weights`. For
+  /// each card, the result holds the total weight of the fillings that give
+  /// it each value, leaving out the weight of that card itself. Also returns
+  /// the total weight of all fillings.
+  fn messages(&self, hint: Hint, weights: &[Weights]) -> (f64, Vec<Weights>) {
+    let len = weights.len();
+    let (sums, voltorbs) = (hint.sum as usize + 1, hint.voltorbs as usize + 1);
+    let index = |sum: usize, voltorbs: usize| voltorbs * sums + sum;
+    let step = |v: usize| if v == 0 { (0, 1) } else { (v, 0) };
+
+    // `prefix[i]` weighs the ways to reach each (sum, voltorbs) pair with the
+    // first `i` cards, and `suffix[i]` with the cards from `i` onwards.
+    let mut prefix = vec![vec![0.0; sums * voltorbs]; len + 1];
+    let mut suffix = prefix.clone();
+    prefix[0][0] = 1.0;
+    suffix[len][0] = 1.0;
+    for (i, r) in Iterator::zip(0..len, (0..len).rev()) {
+      let values = Iterator::zip(weights[i].iter(), &weights[r]).enumerate();
+      for (v, (&wi, &wr)) in values.take(self.max_card as usize + 1) {
+        let (ds, dv) = step(v);
+        for n in dv..voltorbs {
+          for s in ds..sums {
+            let from = index(s - ds, n - dv);
+            prefix[i + 1][index(s, n)] += prefix[i][from] * wi;
+            suffix[r][index(s, n)] += suffix[r + 1][from] * wr;
+          }
+        }
+      }
+    }
+
+    let total = prefix[len][index(sums - 1, voltorbs - 1)];
+    let messages = (0..len)
+      .map(|i| {
+        let mut message = [0.0; 10];
+        for (v, m) in message.iter_mut().enumerate() {
+          let (ds, dv) = step(v);
+          for n in 0..voltorbs.saturating_sub(dv) {
+            for s in 0..sums.saturating_sub(ds) {
+              let rest = index(sums - 1 - ds - s, voltorbs - 1 - dv - n);
+              *m += prefix[i][index(s, n)] * suffix[i + 1][rest];
+            }
+          }
+        }
+        message
+      })
+      .collect();
+
+    (total, messages)
+  }
+
+  /// Calls `out` with every filling of line `y`, the `k`th of the order, that
+  /// could have led to `state`, along with the state it was reached from.
+  fn predecessors(
+    &self,
+    y: usize,
+    k: usize,
+    state: &State,
+    budget: &mut usize,
+    out: &mut dyn FnMut(&Line, State),
+  ) -> bool {
+    // A column entry in the previous state must be reachable with `k` lines.
+    let max = self.max_card as u16;
+    let k = k as u16;
+    let ok = |x: usize, v: u8| {
+      let Some(s) = state[x].checked_sub(pack(v)) else {
+        return false;
+      };
+      let (voltorbs, sum) = (s / VOLTORB, s % VOLTORB);
+      let Some(cards) = k.checked_sub(voltorbs) else {
+        return false;
+      };
+      (cards..=cards * max).contains(&sum)
+    };
+
+    self.fillings(y, &ok, budget, &mut |line| {
+      let mut prev = *state;
+      for (s, &v) in prev.iter_mut().zip(&line[..self.width]) {
+        *s -= pack(v);
+      }
+      out(line, prev)
+    })
+  }
+
+  /// Calls `out` with every filling of line `y` that agrees with its hint,
+  /// such that `ok(x, v)` holds for every value `v` at column `x`.
+  ///
+  /// Each partial filling visited costs one unit of `budget`; returns `false`
+  /// if the budget ran out before all fillings were visited.
+  fn fillings(
+    &self,
+    y: usize,
+    ok: &dyn Fn(usize, u8) -> bool,
+    budget: &mut usize,
+    out: &mut dyn FnMut(&Line),
+  ) -> bool {
+    let hint = self.lines[y];
+    let mut search = Search {
+      grid: self,
+      candidates: &self.candidates[y * self.width..][..self.width],
+      ok,
+      line: [0; MAX_CROSSING],
+      budget,
+      out,
+    };
+    search.fill(0, hint.sum, hint.voltorbs)
+  }
+
+  /// Returns the values the card at column `x` might take, given its value
+  /// if known, ruling out values its crossing line can't accommodate.
+  fn candidates(&self, x: usize, known: Option<u8>) -> u16 {
+    if let Some(v) = known {
+      return 1 << v;
+    }
+
+    let hint = self.crossing[x];
+    let others = self.height as u32 - 1;
+    (0..=self.max_card)
+      .filter(|&v| match v {
+        0 => hint.voltorbs > 0,
+        v => {
+          hint.voltorbs <= others
+            && v as u32 + (others - hint.voltorbs) <= hint.sum
+        }
+      })
+      .fold(0, |acc, v| acc | 1 << v)
+  }
+
+  /// Returns the state every complete board must end up in.
+  fn target(&self) -> State {
+    let mut state = [0; MAX_CROSSING];
+    for (s, hint) in Iterator::zip(state.iter_mut(), self.crossing) {
+      *s = hint.voltorbs as u16 * VOLTORB + hint.sum as u16;
+    }
+    state
+  }
+}
+
+/// Returns the product of two sets of weights.
+fn product(a: &Weights, b: &Weights) -> Weights {
+  let mut out = *a;
+  for (out, b) in Iterator::zip(out.iter_mut(), b) {
+    *out *= b;
+  }
+  out
+}
+
+/// Normalizes `new` and blends it into `old`, returning how far `old` moved.
+///
+/// Blending keeps the messages from oscillating around loops in the board.
+/// Zeros are never blended away, so that impossible values stay impossible.
+fn damp(old: &mut Weights, new: &Weights) -> f64 {
+  let total = new.iter().sum::<f64>();
+  let mut delta = 0f64;
+  for (old, &new) in Iterator::zip(old.iter_mut(), new) {
+    let blended = match new / total {
+      n if n > 0.0 => (*old + n) / 2.0,
+      _ => 0.0,
+    };
+    delta = delta.max((blended - *old).abs());
+    *old = blended;
+  }
+  delta
+}
+
+/// A search for the fillings of a single line; see `Grid::fillings()`.
+struct Search<'a, 'g> {
+  grid: &'a Grid<'g>,
+  candidates: &'a [u16],
+  ok: &'a dyn Fn(usize, u8) -> bool,
+  line: Line,
+  budget: &'a mut usize,
+  out: &'a mut dyn FnMut(&Line),
+}
+
+impl Search<'_, '_> {
+  /// Fills in `line` from `x` onwards, such that the remaining cards add up to
+  /// `sum` and include `voltorbs` Voltorbs.
+  fn fill(&mut self, x: usize, sum: u32, voltorbs: u32) -> bool {
+    if *self.budget == 0 {
+      return false;
+    }
+    *self.budget -= 1;
+
+    let left = (self.grid.width - x) as u32;
+    let Some(cards) = left.checked_sub(voltorbs) else {
+      return true;
+    };
+    if sum < cards || sum > cards * self.grid.max_card as u32 {
+      return true;
+    }
+    if x == self.grid.width {
+      (self.out)(&self.line);
+      return true;
+    }
+
+    for v in 0..=self.grid.max_card {
+      if self.candidates[x] & 1 << v == 0 || !(self.ok)(x, v) {
+        continue;
+      }
+
+      self.line[x] = v;
+      let complete = match v {
+        0 if voltorbs > 0 => self.fill(x + 1, sum, voltorbs - 1),
+        v if v > 0 && v as u32 <= sum => {
+          self.fill(x + 1, sum - v as u32, voltorbs)
+        }
+        _ => true,
+      };
+      if !complete {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+  use std::time::Instant;
+
+  use super::*;
+  use crate::game::Game;
+  use crate::game::Generator;
+  use crate::game::HintSide;
+  use crate::game::Theme;
+
+  /// Deals a board of the given size with `flipped` of its cards face up.
+  fn deal(dims: (u32, u32), max_card: u8, seed: u64, flipped: usize) -> Game {
+    let mut game = Game::new(Options {
+      board_dims: dims,
+      max_card_value: max_card,
+      seed: Some(seed),
+      generator: Generator::Formula,
+      record_rounds: false,
+      theme: Theme::Builtin("default"),
+      card_size: None,
+      layout: None,
+      hint_side: HintSide::BottomRight,
+      enable_debugging: false,
+    });
+    game.generate_board();
+    // Flip a spread of cards, skipping Voltorbs like a lucky player would.
+    let mut left = flipped;
+    for i in (0..game.cards.len()).step_by(2) {
+      if left > 0 && game.cards[i].value != 0 {
+        game.cards[i].flipped = true;
+        left -= 1;
+      }
+    }
+    game
+  }
+
+  fn solve_game(game: &Game) -> Option<Analysis> {
+    solve(&game.options, &game.cards, &game.row_hints, &game.col_hints)
+  }
+
+  /// Computes the odds by trying every value for every face-down card, one
+  /// card at a time, checking each line as soon as it's complete.
+  fn brute_force(game: &Game) -> Vec<[f64; 10]> {
+    struct Search<'a> {
+      game: &'a Game,
+      stride: usize,
+      values: Vec<u8>,
+      counts: Vec<[f64; 10]>,
+      boards: f64,
+    }
+
+    impl Search<'_> {
+      /// Returns the hint that the cards on a line currently add up to.
+      fn hint(&self, cards: impl Iterator<Item = usize>) -> (u32, u32) {
+        cards.fold((0, 0), |(sum, voltorbs), i| {
+          let v = self.values[i];
+          (sum + v as u32, voltorbs + (v == 0) as u32)
+        })
+      }
+
+      fn fill(&mut self, i: usize) {
+        let Search { game, stride, .. } = *self;
+        if i == self.values.len() {
+          self.boards += 1.0;
+          for (count, &v) in Iterator::zip(self.counts.iter_mut(), &self.values)
+          {
+            count[v as usize] += 1.0;
+          }
+          return;
+        }
+
+        let card = game.cards[i];
+        let values = if card.flipped {
+          card.value..=card.value
+        } else {
+          0..=game.options.max_card_value
+        };
+        for v in values {
+          self.values[i] = v;
+          let (x, y) = (i % stride, i / stride);
+          let row = game.row_hints[y];
+          if x == stride - 1
+            && self.hint(y * stride..=i) != (row.sum, row.voltorbs)
+          {
+            continue;
+          }
+          let col = game.col_hints[x];
+          if i + stride >= self.values.len()
+            && self.hint((x..=i).step_by(stride)) != (col.sum, col.voltorbs)
+          {
+            continue;
+          }
+          self.fill(i + 1);
+        }
+      }
+    }
+
+    let len = game.cards.len();
+    let mut search = Search {
+      game,
+      stride: game.options.board_dims.0 as usize,
+      values: vec![0; len],
+      counts: vec![[0.0; 10]; len],
+      boards: 0.0,
+    };
+    search.fill(0);
+    for count in &mut search.counts {
+      for p in count {
+        *p /= search.boards;
+      }
+    }
+    search.counts
+  }
+
+  #[test]
+  fn matches_brute_force() {
+    let boards = [
+      ((3, 3), 3, 0),
+      ((4, 3), 3, 3),
+      ((3, 4), 4, 4),
+      ((4, 4), 3, 7),
+      ((4, 4), 5, 0),
+      ((5, 5), 3, 6),
+    ];
+    for (dims, max_card, flipped) in boards {
+      for seed in 0..3 {
+        let game = deal(dims, max_card, seed, flipped);
+        let analysis = solve_game(&game).unwrap();
+        assert!(analysis.exact);
+        for (i, (odds, expected)) in
+          Iterator::zip(analysis.odds.iter(), brute_force(&game)).enumerate()
+        {
+          for v in 0..10 {
+            assert!(
+              (odds.values[v] - expected[v]).abs() < 1e-9,
+              "{dims:?} seed {seed}, card {i}: {:?} != {expected:?}",
+              odds.values,
+            );
+            assert_eq!(odds.possible & (1 << v) != 0, expected[v] > 0.0);
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn inconsistent_hints() {
+    let mut game = deal((5, 5), 3, 1, 0);
+    game.row_hints[0].sum += 1;
+    assert!(solve_game(&game).is_none());
+
+    let mut game = deal((5, 5), 3, 1, 0);
+    game.col_hints[2].voltorbs = 6;
+    assert!(solve_game(&game).is_none());
+
+    // A flipped card worth more than its whole row.
+    let mut game = deal((5, 5), 3, 1, 0);
+    game.cards[0].value = 3;
+    game.cards[0].flipped = true;
+    game.row_hints[0].sum = 2;
+    assert!(solve_game(&game).is_none());
+  }
+
+  #[test]
+  fn big_boards_are_fast() {
+    for seed in 0..2 {
+      for level in [1, 8] {
+        let mut game = deal((8, 8), 9, seed, 0);
+        game.level = level;
+        game.generate_board();
+
+        let start = Instant::now();
+        let analysis = solve_game(&game).unwrap();
+        let elapsed = start.elapsed();
+        assert!(
+          elapsed < Duration::from_secs(10),
+          "seed {seed}, level {level}: took {elapsed:?}",
+        );
+
+        // Even an estimate never rules out a card's real value.
+        for (odds, card) in Iterator::zip(analysis.odds.iter(), &game.cards) {
+          assert!(odds.possible & (1 << card.value) != 0);
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,522 @@
+//! Records of finished rounds, and statistics about them.
+//!
+//! Every finished round is appended to a log in the user's data directory, one
+//! JSON object per line, so that a crash can at worst lose the round being
+//! written.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::io::Write as _;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::game::parse_name;
+use crate::game::save;
+use crate::game::Game;
+use crate::game::Generator;
+use crate::game::Outcome;
+
+/// The name of the round log in the data directory.
+const LOG: &str = "rounds.jsonl";
+
+/// A record of one finished round.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Record {
+  /// When the round finished, in seconds since the Unix epoch.
+  time: u64,
+  board_dims: (u32, u32),
+  max_card_value: u8,
+  generator: Generator,
+  level: u32,
+  outcome: Outcome,
+  /// The coins the player kept.
+  coins: u64,
+  /// The number of cards flipped, not counting Voltorbs.
+  flipped: u32,
+}
+
+impl Record {
+  /// Creates a record of a round that just finished.
+  pub(super) fn new(
+    options: &super::Options,
+    level: u32,
+    outcome: Outcome,
+    coins: u64,
+    flipped: u32,
+  ) -> Self {
+    let time = SystemTime::now()
+      .duration_since(SystemTime::UNIX_EPOCH)
+      .map_or(0, |d| d.as_secs());
+    Self {
+      time,
+      board_dims: options.board_dims,
+      max_card_value: options.max_card_value,
+      generator: options.generator,
+      level,
+      outcome,
+      coins,
+      flipped,
+    }
+  }
+
+  /// Appends this record to the round log.
+  pub(super) fn append(&self) -> io::Result<()> {
+    let path = save::data_file(LOG)?;
+    let mut log = fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(path)?;
+    writeln!(log, "{}", serde_json::to_string(self)?)
+  }
+
+  /// Reads every record in the round log.
+  ///
+  /// Lines that can't be parsed, such as a line cut off by a crash, are
+  /// skipped.
+  pub(super) fn read_all() -> io::Result<Vec<Record>> {
+    let Some(dir) = save::data_dir() else {
+      return Ok(Vec::new());
+    };
+    let log = match fs::read_to_string(dir.join(LOG)) {
+      Ok(log) => log,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+      Err(e) => return Err(e),
+    };
+    Ok(
+      log
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect(),
+    )
+  }
+}
+
+/// One round, as `voltorb stats --rounds` writes it out.
+///
+/// This names things the same way the summaries and the command line do,
+/// rather than the way the round log does.
+#[derive(Clone, Debug, Serialize)]
+struct Round {
+  time: u64,
+  columns: u32,
+  rows: u32,
+  max_card: u8,
+  generator: &'static str,
+  level: u32,
+  outcome: String,
+  coins: u64,
+  flipped: u32,
+}
+
+impl From<&Record> for Round {
+  fn from(r: &Record) -> Self {
+    Self {
+      time: r.time,
+      columns: r.board_dims.0,
+      rows: r.board_dims.1,
+      max_card: r.max_card_value,
+      generator: r.generator.name(),
+      level: r.level,
+      outcome: r.outcome.to_string(),
+      coins: r.coins,
+      flipped: r.flipped,
+    }
+  }
+}
+
+/// A board configuration that rounds are grouped by.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct Config {
+  board_dims: (u32, u32),
+  max_card_value: u8,
+  generator: &'static str,
+}
+
+/// Statistics for all rounds played with one board configuration.
+#[derive(Clone, Debug, Default, Serialize)]
+struct Summary {
+  columns: u32,
+  rows: u32,
+  max_card: u8,
+  generator: &'static str,
+  rounds: u64,
+  wins: u64,
+  losses: u64,
+  quits: u64,
+  /// The coins kept over all rounds.
+  coins: u64,
+  /// The most coins kept in a single round.
+  best: u64,
+  /// The highest level reached.
+  top_level: u32,
+  /// The average number of cards flipped per round.
+  flipped: f64,
+}
+
+/// Groups `records` by board configuration and summarizes each group.
+fn summarize(records: &[Record]) -> Vec<Summary> {
+  let mut groups = BTreeMap::<Config, Summary>::new();
+  for r in records {
+    let config = Config {
+      board_dims: r.board_dims,
+      max_card_value: r.max_card_value,
+      generator: r.generator.name(),
+    };
+    let s = groups.entry(config).or_insert_with(|| Summary {
+      columns: r.board_dims.0,
+      rows: r.board_dims.1,
+      max_card: r.max_card_value,
+      generator: config.generator,
+      ..Summary::default()
+    });
+
+    s.rounds += 1;
+    match r.outcome {
+      Outcome::Win => s.wins += 1,
+      Outcome::Loss => s.losses += 1,
+      Outcome::Quit => s.quits += 1,
+    }
+    s.coins += r.coins;
+    s.best = s.best.max(r.coins);
+    s.top_level = s.top_level.max(r.level);
+    // Keep a running sum for now; it's turned into an average below.
+    s.flipped += r.flipped as f64;
+  }
+
+  let mut summaries = groups.into_values().collect::<Vec<_>>();
+  for s in &mut summaries {
+    s.flipped /= s.rounds as f64;
+  }
+  summaries
+}
+
+/// Formats `summaries` as a table, one line per configuration.
+fn table(summaries: &[Summary]) -> Vec<String> {
+  let boards = summaries
+    .iter()
+    .map(|s| board(s.columns, s.rows, s.max_card, s.generator))
+    .collect::<Vec<_>>();
+  let width = board_width(&boards);
+
+  let mut lines = vec![format!(
+    "{:<width$}  {:>6}  {:>6}  {:>5}  {:>5}  {:>9}  {:>7}  {:>3}",
+    "board", "rounds", "win%", "quits", "flips", "coins", "best", "lvl",
+  )];
+  for (s, board) in summaries.iter().zip(boards) {
+    lines.push(format!(
+      "{:<width$}  {:>6}  {:>5.1}%  {:>5}  {:>5.1}  {:>9}  {:>7}  {:>3}",
+      board,
+      s.rounds,
+      s.wins as f64 / s.rounds as f64 * 100.0,
+      s.quits,
+      s.flipped,
+      s.coins,
+      s.best,
+      s.top_level,
+    ));
+  }
+  lines
+}
+
+/// Labels a board configuration, like `5x5/3 formula`.
+fn board(columns: u32, rows: u32, max_card: u8, generator: &str) -> String {
+  format!("{columns}x{rows}/{max_card} {generator}")
+}
+
+/// Returns how wide a column of board labels needs to be.
+fn board_width(boards: &[String]) -> usize {
+  boards
+    .iter()
+    .map(String::len)
+    .fold("board".len(), usize::max)
+}
+
+/// An output format for `voltorb stats`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Format {
+  /// Aligned columns, for reading.
+  Table,
+  /// A pretty-printed JSON array, with one object per row.
+  Json,
+  /// Comma-separated values, with a header row, for spreadsheets.
+  Csv,
+}
+
+impl Format {
+  const ALL: [(Format, &'static str); 3] = [
+    (Self::Table, "table"),
+    (Self::Json, "json"),
+    (Self::Csv, "csv"),
+  ];
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    parse_name(&Self::ALL, s)
+  }
+}
+
+/// Formats the round log in `format`; either every round, or a summary of
+/// them per board configuration.
+pub fn report(format: Format, rounds: bool) -> io::Result<String> {
+  write_report(&Record::read_all()?, format, rounds)
+}
+
+/// Formats `records` like `report()` does.
+fn write_report(
+  records: &[Record],
+  format: Format,
+  rounds: bool,
+) -> io::Result<String> {
+  let summaries = summarize(records);
+
+  let mut out = String::new();
+  match (format, rounds) {
+    (Format::Json, true) => {
+      let rounds = records.iter().map(Round::from).collect::<Vec<_>>();
+      out = serde_json::to_string_pretty(&rounds)? + "\n";
+    }
+    (Format::Json, false) => {
+      out = serde_json::to_string_pretty(&summaries)? + "\n";
+    }
+    (Format::Table, false) => {
+      for line in table(&summaries) {
+        let _ = writeln!(out, "{line}");
+      }
+    }
+
+    (Format::Table, true) => {
+      let boards = records
+        .iter()
+        .map(|r| {
+          let (columns, rows) = r.board_dims;
+          board(columns, rows, r.max_card_value, r.generator.name())
+        })
+        .collect::<Vec<_>>();
+      let width = board_width(&boards);
+
+      let _ = writeln!(
+        out,
+        "{:>10}  {:<width$}  {:>5}  {:>7}  {:>7}  {:>5}",
+        "time", "board", "level", "outcome", "coins", "flips",
+      );
+      for (r, board) in records.iter().zip(boards) {
+        let _ = writeln!(
+          out,
+          "{:>10}  {:<width$}  {:>5}  {:>7}  {:>7}  {:>5}",
+          r.time, board, r.level, r.outcome, r.coins, r.flipped,
+        );
+      }
+    }
+
+    // Every field is a number or a simple name, so none of them need quoting.
+    (Format::Csv, true) => {
+      let _ = writeln!(
+        out,
+        "time,columns,rows,max_card,generator,level,outcome,coins,flipped"
+      );
+      for r in records {
+        let _ = writeln!(
+          out,
+          "{},{},{},{},{},{},{},{},{}",
+          r.time,
+          r.board_dims.0,
+          r.board_dims.1,
+          r.max_card_value,
+          r.generator.name(),
+          r.level,
+          r.outcome,
+          r.coins,
+          r.flipped,
+        );
+      }
+    }
+    (Format::Csv, false) => {
+      let _ = writeln!(
+        out,
+        "columns,rows,max_card,generator,rounds,wins,losses,quits,coins,\
+         best,top_level,flipped"
+      );
+      for s in &summaries {
+        let _ = writeln!(
+          out,
+          "{},{},{},{},{},{},{},{},{},{},{},{:.2}",
+          s.columns,
+          s.rows,
+          s.max_card,
+          s.generator,
+          s.rounds,
+          s.wins,
+          s.losses,
+          s.quits,
+          s.coins,
+          s.best,
+          s.top_level,
+          s.flipped,
+        );
+      }
+    }
+  }
+  Ok(out)
+}
+
+impl Game {
+  /// Opens the statistics screen, or tells the player why it can't be.
+  pub(super) fn show_stats(&mut self) {
+    match Record::read_all() {
+      Ok(records) if records.is_empty() => {
+        self.notice = Some("No rounds recorded yet.".to_string());
+      }
+      Ok(records) => self.stats = Some(table(&summarize(&records))),
+      Err(e) => self.notice = Some(format!("Could not read stats: {e}")),
+    }
+  }
+}
+
+impl fmt::Display for Outcome {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.pad(match self {
+      Self::Win => "win",
+      Self::Loss => "loss",
+      Self::Quit => "quit",
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record(
+    board_dims: (u32, u32),
+    level: u32,
+    outcome: Outcome,
+    coins: u64,
+    flipped: u32,
+  ) -> Record {
+    Record {
+      time: 1_600_000_000 + flipped as u64,
+      board_dims,
+      max_card_value: 3,
+      generator: Generator::Formula,
+      level,
+      outcome,
+      coins,
+      flipped,
+    }
+  }
+
+  fn records() -> Vec<Record> {
+    vec![
+      record((5, 5), 1, Outcome::Win, 24, 4),
+      record((5, 5), 2, Outcome::Loss, 0, 1),
+      record((8, 8), 1, Outcome::Quit, 6, 2),
+      record((5, 5), 1, Outcome::Quit, 12, 3),
+    ]
+  }
+
+  #[test]
+  fn summaries() {
+    let summaries = summarize(&records());
+    assert_eq!(summaries.len(), 2);
+
+    let s = &summaries[0];
+    assert_eq!((s.columns, s.rows, s.max_card), (5, 5, 3));
+    assert_eq!((s.rounds, s.wins, s.losses, s.quits), (3, 1, 1, 1));
+    assert_eq!((s.coins, s.best, s.top_level), (36, 24, 2));
+    assert_eq!(s.flipped, 8.0 / 3.0);
+
+    let s = &summaries[1];
+    assert_eq!((s.columns, s.rows), (8, 8));
+    assert_eq!((s.rounds, s.quits, s.coins), (1, 1, 6));
+  }
+
+  #[test]
+  fn formats() {
+    let report =
+      |format, rounds| write_report(&records()[..2], format, rounds).unwrap();
+
+    assert_eq!(
+      report(Format::Table, false),
+      "\
+board          rounds    win%  quits  flips      coins     best  lvl
+5x5/3 formula       2   50.0%      0    2.5         24       24    2
+",
+    );
+    assert_eq!(
+      report(Format::Table, true),
+      [
+        "      time  board          level  outcome    coins  flips\n",
+        "1600000004  5x5/3 formula      1      win       24      4\n",
+        "1600000001  5x5/3 formula      2     loss        0      1\n",
+      ]
+      .concat(),
+    );
+
+    assert_eq!(
+      report(Format::Csv, false),
+      "\
+columns,rows,max_card,generator,rounds,wins,losses,quits,coins,best,top_level,flipped
+5,5,3,formula,2,1,1,0,24,24,2,2.50
+",
+    );
+    assert_eq!(
+      report(Format::Csv, true),
+      "\
+time,columns,rows,max_card,generator,level,outcome,coins,flipped
+1600000004,5,5,3,formula,1,win,24,4
+1600000001,5,5,3,formula,2,loss,0,1
+",
+    );
+
+    // JSON is checked by reading it back, rather than by its whitespace.
+    let json = |rounds| {
+      serde_json::from_str::<serde_json::Value>(&report(Format::Json, rounds))
+        .unwrap()
+    };
+    assert_eq!(json(false)[0]["rounds"], 2);
+    assert_eq!(json(false)[0]["generator"], "formula");
+    assert_eq!(json(false)[0]["flipped"], 2.5);
+    let rounds = json(true);
+    assert_eq!(rounds.as_array().unwrap().len(), 2);
+    assert_eq!(rounds[0]["outcome"], "win");
+    assert_eq!(rounds[0]["generator"], "formula");
+    assert_eq!(rounds[1]["columns"], 5);
+    assert_eq!(rounds[1]["rows"], 5);
+    assert_eq!(rounds[1]["max_card"], 3);
+    assert!(records()[..2]
+      .iter()
+      .zip(rounds.as_array().unwrap())
+      .all(|(r, json)| json["coins"] == r.coins));
+  }
+
+  #[test]
+  fn wide_boards_line_up() {
+    let mut records = records();
+    records[2].max_card_value = 9;
+    records[2].board_dims = (16, 16);
+    let table = write_report(&records, Format::Table, false).unwrap();
+    let lines = table.lines().collect::<Vec<_>>();
+    assert!(lines[0].starts_with("board            rounds"));
+    assert!(lines[1].starts_with("5x5/3 formula         3"));
+    assert!(lines[2].starts_with("16x16/9 formula       1"));
+    // Every column ends in the same place on every line.
+    assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+  }
+
+  #[test]
+  fn format_names() {
+    for (format, name) in Format::ALL {
+      assert_eq!(name.parse::<Format>(), Ok(format));
+    }
+    assert!("yaml".parse::<Format>().is_err());
+  }
+}
@@ -0,0 +1,841 @@
+//! End-to-end tests, which play whole rounds through a [`MockTty`].
+//!
+//! Some tests compare the screen against golden files in `testdata`. After a
+//! deliberate change to how the game looks, rerun the tests with
+//! `VOLTORB_BLESS=1` set to rewrite them, and review the diff.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use enumflags2::BitFlags;
+
+use crate::game::anim::Effect;
+use crate::game::anim::Entity;
+use crate::game::gfx;
+use crate::game::CardSize;
+use crate::game::Game;
+use crate::game::Generator;
+use crate::game::HintSide;
+use crate::game::ManualClock;
+use crate::game::Options;
+use crate::game::Outcome;
+use crate::game::State;
+use crate::game::Theme;
+use crate::term::Canvas;
+use crate::term::Cell;
+use crate::term::Event;
+use crate::term::Key;
+use crate::term::MockTty;
+use crate::term::MouseAction;
+use crate::term::Tty;
+
+/// The most frames any test waits for the game to settle down.
+const MAX_FRAMES: usize = 10_000;
+/// How much time passes each frame, unless a test says otherwise.
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 30);
+
+/// Returns the options for a game on a board of the given size, with cards up
+/// to 3, that doesn't touch the disk.
+pub(super) fn options(board_dims: (u32, u32), seed: u64) -> Options {
+  Options {
+    board_dims,
+    max_card_value: 3,
+    seed: Some(seed),
+    generator: Generator::Formula,
+    record_rounds: false,
+    theme: Theme::Builtin("default"),
+    card_size: None,
+    layout: None,
+    hint_side: HintSide::BottomRight,
+    enable_debugging: false,
+  }
+}
+
+/// A game hooked up to a fake terminal, run the same way `main` runs it.
+struct Harness {
+  game: Game,
+  tty: MockTty,
+  canvas: Canvas,
+  clock: ManualClock,
+}
+
+impl Harness {
+  /// Starts a game on a vanilla board with the given seed, and runs it until
+  /// the first board is dealt.
+  fn new(seed: u64) -> Self {
+    Self::with_dims(seed, (5, 5), Cell::from_xy(100, 40))
+  }
+
+  /// Like `new()`, but with a board and a screen of the given sizes.
+  fn with_dims(seed: u64, board_dims: (u32, u32), viewport: Cell) -> Self {
+    let clock = ManualClock::default();
+    let options = options(board_dims, seed);
+    let mut game = Game::with_clock(options, Box::new(clock.clone()));
+    game.resize(viewport);
+
+    let mut h = Self {
+      game,
+      tty: MockTty::new(viewport),
+      canvas: Canvas::new(viewport),
+      clock,
+    };
+    h.settle();
+    h
+  }
+
+  /// Runs a single frame, feeding the game every scripted event that's
+  /// pending.
+  ///
+  /// Returns whether the game wants to keep going.
+  fn frame(&mut self) -> bool {
+    self.frame_after(FRAME)
+  }
+
+  /// Lets `time` pass, and then runs a single frame.
+  fn frame_after(&mut self, time: Duration) -> bool {
+    self.clock.advance(time);
+    let mut events = Vec::new();
+    while let Some(event) = self.tty.poll(None).unwrap() {
+      events.push(Some(event));
+    }
+    if events.is_empty() {
+      events.push(None);
+    }
+
+    let mut running = true;
+    for event in events {
+      if let Some(Event::Winch(vp)) = event {
+        self.canvas.winch(vp);
+        self.game.resize(vp);
+      } else if !self.game.interact(event) {
+        running = false;
+        break;
+      }
+    }
+    let layers = self.game.render(self.canvas.viewport());
+    self.canvas.render(layers, &mut self.tty).unwrap();
+    running
+  }
+
+  /// Runs frames until the game is waiting on the player again.
+  fn settle(&mut self) {
+    for _ in 0..MAX_FRAMES {
+      if self.game.is_idle() && self.tty.pending() == 0 {
+        return;
+      }
+      self.frame();
+    }
+    panic!("game did not settle within {MAX_FRAMES} frames");
+  }
+
+  /// Presses `key`, and lets the game react to it.
+  fn press(&mut self, key: Key) {
+    self.tty.script([Event::Key {
+      key,
+      mods: BitFlags::empty(),
+    }]);
+    self.settle();
+  }
+
+  /// Moves the selection to the card at `idx` with the arrow keys.
+  fn select(&mut self, idx: usize) {
+    let (cols, rows) = self.game.options.board_dims;
+    let (cols, rows) = (cols as usize, rows as usize);
+    let from = self.game.selected_card;
+    let downs = (idx / cols + rows - from / cols) % rows;
+    let rights = (idx % cols + cols - from % cols) % cols;
+    for _ in 0..downs {
+      self.press(Key::Down);
+    }
+    for _ in 0..rights {
+      self.press(Key::Right);
+    }
+    assert_eq!(self.game.selected_card, idx);
+  }
+
+  /// Selects and flips the card at `idx`.
+  fn flip(&mut self, idx: usize) {
+    self.select(idx);
+    self.press(Key::Enter);
+  }
+
+  /// Flips the card at `idx`, and runs until the round it ends is revealed
+  /// and waiting for the player to move on.
+  fn flip_last(&mut self, idx: usize) {
+    self.select(idx);
+    self.end_round_with(Key::Enter);
+  }
+
+  /// Presses `key`, which should end the round, and runs until the board is
+  /// revealed and waiting for the player to move on.
+  fn end_round_with(&mut self, key: Key) {
+    self.tty.script([Event::Key {
+      key,
+      mods: BitFlags::empty(),
+    }]);
+    for _ in 0..MAX_FRAMES {
+      if self.game.timeline.current().is_some_and(|e| e.input_ends) {
+        return;
+      }
+      self.frame();
+    }
+    panic!("round did not end within {MAX_FRAMES} frames");
+  }
+
+  /// Returns the indices of the cards with values matching `pred`.
+  fn cards(&self, pred: impl Fn(u8) -> bool) -> Vec<usize> {
+    let cards = self.game.cards.iter().enumerate();
+    cards
+      .filter(|(_, c)| pred(c.value))
+      .map(|(i, _)| i)
+      .collect()
+  }
+}
+
+/// Checks `actual` against the golden file `testdata/{name}`.
+fn assert_golden(name: &str, actual: &str) {
+  let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    .join("src/game/testdata")
+    .join(name);
+  if std::env::var_os("VOLTORB_BLESS").is_some() {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, actual).unwrap();
+    return;
+  }
+
+  let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+    panic!(
+      "could not read {}: {e}; run with VOLTORB_BLESS=1",
+      path.display()
+    )
+  });
+  assert!(
+    expected == actual,
+    "screen does not match {}\n--- expected\n{expected}\n--- actual\n{actual}",
+    path.display(),
+  );
+}
+
+#[test]
+fn first_board() {
+  let h = Harness::new(1);
+  assert!(matches!(h.game.state, State::Standby));
+  assert_golden("first_board.txt", &h.tty.to_plain());
+  assert_golden("first_board.ansi", &h.tty.to_ansi());
+}
+
+#[test]
+fn win_round() {
+  let mut h = Harness::new(1);
+  let multipliers = h.cards(|v| v > 1);
+  let (&last, rest) = multipliers.split_last().unwrap();
+  for &idx in rest {
+    h.flip(idx);
+    assert!(matches!(h.game.state, State::Standby));
+  }
+
+  let coins = multipliers
+    .iter()
+    .map(|&i| h.game.cards[i].value as u64)
+    .product::<u64>();
+  h.flip_last(last);
+  assert!(matches!(
+    h.game.state,
+    State::GameOver {
+      new_level: 2,
+      outcome: Outcome::Win,
+    }
+  ));
+  assert_eq!(h.game.round_score, coins);
+  assert_golden("win_round.txt", &h.tty.to_plain());
+
+  // Any key moves on to the next round.
+  h.press(Key::Glyph(' '));
+  assert!(matches!(h.game.state, State::Standby));
+  assert_eq!(h.game.level, 2);
+  assert_eq!(h.game.score, coins);
+  assert!(h.game.cards.iter().all(|c| !c.flipped));
+}
+
+#[test]
+fn lose_round() {
+  let mut h = Harness::new(1);
+  let multiplier = h.cards(|v| v > 1)[0];
+  let voltorb = h.cards(|v| v == 0)[0];
+  h.flip(multiplier);
+  assert!(h.game.round_score > 1);
+
+  h.flip_last(voltorb);
+  assert!(matches!(
+    h.game.state,
+    State::GameOver {
+      outcome: Outcome::Loss,
+      ..
+    }
+  ));
+  assert!(h.game.cards.iter().all(|c| c.flipped));
+
+  h.press(Key::Glyph(' '));
+  assert_eq!(h.game.level, 1);
+  assert_eq!(h.game.score, 0);
+}
+
+#[test]
+fn quit_round() {
+  let mut h = Harness::new(1);
+  let multiplier = h.cards(|v| v > 1)[0];
+  h.flip(multiplier);
+  let coins = h.game.round_score;
+
+  h.press(Key::Glyph('b'));
+  assert!(matches!(h.game.state, State::ConfirmQuit));
+  h.press(Key::Glyph('n'));
+  assert!(matches!(h.game.state, State::Standby));
+
+  h.press(Key::Glyph('b'));
+  h.press(Key::Glyph('y'));
+  h.press(Key::Glyph(' '));
+  assert!(matches!(h.game.state, State::Standby));
+  assert_eq!(h.game.score, coins);
+}
+
+/// Finds a cell on the screen that belongs to the card at `idx`, if it's on
+/// screen at all.
+fn find_card(h: &Harness, idx: usize) -> Option<Cell> {
+  // Every card is bigger than 4x2, so there's no need to look everywhere.
+  let frame = h.game.frame.as_ref().unwrap();
+  let (x, y) = h.canvas.viewport().xy();
+  (0..y)
+    .step_by(2)
+    .flat_map(|row| (0..x).step_by(4).map(move |col| Cell::from_xy(col, row)))
+    .find(|&cell| frame.target_at(cell) == Some(gfx::Target::Card(idx)))
+}
+
+#[test]
+fn flip_with_mouse() {
+  let mut h = Harness::new(1);
+  let idx = h.cards(|v| v > 1)[0];
+  let cell = find_card(&h, idx).unwrap();
+
+  h.tty.script([Event::Mouse {
+    button: Some(0),
+    cell,
+    action: MouseAction::Press,
+    mods: BitFlags::empty(),
+  }]);
+  h.settle();
+  assert_eq!(h.game.selected_card, idx);
+  assert!(h.game.cards[idx].flipped);
+}
+
+#[test]
+fn hover_selects() {
+  let mut h = Harness::new(1);
+  let hover = |cell| Event::Mouse {
+    button: None,
+    cell,
+    action: MouseAction::Move,
+    mods: BitFlags::empty(),
+  };
+  assert!(!h.game.needs_render());
+
+  // Moving around within the selected card changes nothing on screen.
+  let cell = find_card(&h, 0).unwrap();
+  h.game.interact(Some(hover(cell)));
+  h.game
+    .interact(Some(hover(Cell::from_xy(cell.col() + 1, cell.row()))));
+  assert!(!h.game.needs_render());
+
+  let cell = find_card(&h, 6).unwrap();
+  h.game.interact(Some(hover(cell)));
+  assert_eq!(h.game.selected_card, 6);
+  assert!(h.game.needs_render());
+}
+
+#[test]
+fn selection_wraps() {
+  let mut h = Harness::new(1);
+  for _ in 0..5 {
+    h.press(Key::Up);
+    assert!(h.game.selected_card < h.game.cards.len());
+  }
+  assert_eq!(h.game.selected_card, 0);
+  for _ in 0..5 {
+    h.press(Key::Down);
+    assert!(h.game.selected_card < h.game.cards.len());
+  }
+  assert_eq!(h.game.selected_card, 0);
+  h.press(Key::Left);
+  assert_eq!(h.game.selected_card, 4);
+  h.press(Key::Right);
+  assert_eq!(h.game.selected_card, 0);
+}
+
+#[test]
+fn idle_until_input() {
+  let mut h = Harness::new(1);
+  assert!(h.game.is_idle());
+
+  // Idle frames change nothing.
+  let before = h.tty.to_plain();
+  h.frame();
+  assert_eq!(h.tty.to_plain(), before);
+
+  // A flip animates until it's done, and then the game is idle again.
+  let idx = h.cards(|v| v > 1)[0];
+  h.select(idx);
+  h.tty.script([Event::Key {
+    key: Key::Enter,
+    mods: BitFlags::empty(),
+  }]);
+  h.frame();
+  assert!(!h.game.is_idle());
+  h.settle();
+  assert!(h.game.cards[idx].flipped);
+}
+
+#[test]
+fn animations_ignore_frame_rate() {
+  // Flip the same card in two games, one at a high frame rate and one at a
+  // low one, and catch both halfway through the flip.
+  let screens =
+    [Duration::from_millis(5), Duration::from_millis(50)].map(|frame| {
+      let mut h = Harness::new(1);
+      let idx = h.cards(|v| v > 1)[0];
+      h.select(idx);
+      h.tty.script([Event::Key {
+        key: Key::Enter,
+        mods: BitFlags::empty(),
+      }]);
+      h.frame_after(Duration::ZERO);
+      for _ in 0..100 / frame.as_millis() {
+        h.frame_after(frame);
+      }
+      assert_eq!(h.game.animation(Entity::Card(idx), Effect::Flip), Some(3));
+      h.tty.to_plain()
+    });
+  assert_eq!(screens[0], screens[1]);
+
+  // The round ends after the board has been revealed for a while, even if the
+  // player doesn't press anything.
+  let mut h = Harness::new(1);
+  h.flip_last(h.cards(|v| v == 0)[0]);
+  h.frame_after(Duration::from_secs(4));
+  assert!(matches!(h.game.state, State::GameOver { .. }));
+  h.frame_after(Duration::from_secs(1));
+  h.settle();
+  assert!(matches!(h.game.state, State::Standby));
+  assert!(h.game.cards.iter().all(|c| !c.flipped));
+}
+
+#[test]
+fn quit_ends_loop() {
+  let mut h = Harness::new(1);
+  h.tty.script([Event::Key {
+    key: Key::Glyph('q'),
+    mods: BitFlags::empty(),
+  }]);
+  assert!(!h.frame());
+}
+
+#[test]
+fn winch_redraws() {
+  let mut h = Harness::new(1);
+  let before = h.tty.to_plain();
+  h.tty.script([Event::Winch(Cell::from_xy(100, 40))]);
+  h.frame();
+  assert_eq!(h.tty.to_plain(), before);
+}
+
+#[test]
+fn too_small_pauses_until_enlarged() {
+  let mut h = Harness::new(1);
+  let before = h.tty.to_plain();
+
+  h.tty.script([Event::Winch(Cell::from_xy(40, 12))]);
+  h.frame();
+  let screen = h.tty.to_plain();
+  assert!(screen.contains("Terminal too small"), "{screen}");
+  assert!(screen.contains("Have 40x12"), "{screen}");
+  assert!(!screen.contains('╭'), "{screen}");
+
+  // Nothing the player does goes through while they can't see the board.
+  h.press(Key::Right);
+  h.press(Key::Enter);
+  assert_eq!(h.game.selected_card, 0);
+  assert!(h.game.cards.iter().all(|c| !c.flipped));
+
+  h.tty.script([Event::Winch(Cell::from_xy(100, 40))]);
+  h.frame();
+  assert_eq!(h.tty.to_plain(), before);
+  h.press(Key::Right);
+  assert_eq!(h.game.selected_card, 1);
+}
+
+#[test]
+fn wide_dialogs_dont_need_room() {
+  let mut h = Harness::with_dims(1, (5, 5), Cell::from_xy(60, 24));
+  assert!(!h.tty.to_plain().contains("Terminal too small"));
+
+  // The statistics table is wider than the screen, but that only clips it;
+  // it can still be closed.
+  h.game.stats = Some(vec![format!("{:-<70}|", "16x16/9 formula")]);
+  h.frame();
+  let screen = h.tty.to_plain();
+  assert!(!screen.contains("Terminal too small"), "{screen}");
+  assert!(screen.contains("Rounds played, by board"), "{screen}");
+  h.press(Key::Glyph('x'));
+  assert!(h.game.stats.is_none());
+  assert!(!h.tty.to_plain().contains("Rounds played"));
+}
+
+/// Returns the event for pressing `key` on its own.
+fn key(key: Key) -> Event {
+  Event::Key {
+    key,
+    mods: BitFlags::empty(),
+  }
+}
+
+#[test]
+fn input_during_flip_is_buffered() {
+  let mut h = Harness::new(1);
+  let idx = h.cards(|v| v > 1)[0];
+  h.select(idx);
+
+  // Everything typed while the card is still turning over lands afterwards,
+  // in order.
+  h.tty.script([key(Key::Enter)]);
+  h.frame();
+  assert!(!h.game.is_idle());
+  h.tty
+    .script([key(Key::Glyph('2')), key(Key::Right), key(Key::Down)]);
+  h.frame();
+  assert_eq!(h.game.selected_card, idx);
+  h.settle();
+
+  let stride = h.game.options.board_dims.0 as usize;
+  let expected = (idx / stride + 1) % 5 * stride + (idx + 1) % stride;
+  assert!(h.game.cards[idx].flipped);
+  assert_eq!(h.game.selected_card, expected);
+  assert_eq!(h.game.cards[idx].memo, 1 << 2);
+}
+
+#[test]
+fn menus_wait_their_turn() {
+  let mut h = Harness::new(1);
+  let idx = h.cards(|v| v > 1)[0];
+  h.select(idx);
+  h.tty.script([key(Key::Enter)]);
+  h.frame();
+
+  // A menu key typed during the flip is dropped, but the player is told so.
+  h.tty.script([key(Key::Right), key(Key::Glyph('b'))]);
+  h.frame();
+  assert!(h.tty.to_plain().contains("Busy: try that key again."));
+  h.tty.script([key(Key::Right)]);
+  h.frame();
+
+  // Once the flip is over, the queue drains; a menu key that comes in while
+  // it does goes after the moves ahead of it.
+  while h.game.queued.len() == 2 {
+    h.frame();
+  }
+  h.tty.script([key(Key::Glyph('b'))]);
+  h.settle();
+  assert!(matches!(h.game.state, State::ConfirmQuit));
+  let stride = h.game.options.board_dims.0 as usize;
+  assert_eq!(
+    h.game.selected_card,
+    idx / stride * stride + (idx + 2) % stride
+  );
+}
+
+#[test]
+fn quit_skips_the_queue() {
+  let mut h = Harness::new(1);
+  h.select(h.cards(|v| v > 1)[0]);
+  h.tty.script([key(Key::Enter)]);
+  h.frame();
+  h.tty.script([key(Key::Right), key(Key::Glyph('q'))]);
+  assert!(!h.frame());
+}
+
+#[test]
+fn queued_input_ends_with_round() {
+  let mut h = Harness::new(1);
+  let voltorb = h.cards(|v| v == 0)[0];
+  h.select(voltorb);
+
+  // Keys mashed while the losing flip plays out are meant for the old board.
+  h.tty
+    .script([key(Key::Enter), key(Key::Right), key(Key::Enter)]);
+  for _ in 0..MAX_FRAMES {
+    if matches!(h.game.state, State::GameOver { .. }) {
+      break;
+    }
+    h.frame();
+  }
+  h.press(Key::Glyph(' '));
+  assert!(matches!(h.game.state, State::Standby));
+  assert!(h.game.cards.iter().all(|c| !c.flipped));
+  assert_eq!(h.game.selected_card, voltorb);
+}
+
+#[test]
+fn boards_at_every_size() {
+  for dims in [(3, 3), (3, 16), (16, 3), (16, 16)] {
+    let mut h = Harness::with_dims(1, dims, Cell::from_xy(100, 40));
+    for level in 1..=8 {
+      h.game.level = level;
+      h.game.generate_board();
+      let voltorbs = h.cards(|v| v == 0).len();
+      let multipliers = h.cards(|v| v > 1).len();
+      let ones = h.cards(|v| v == 1).len();
+      assert!(voltorbs > 0, "{dims:?} level {level} has no Voltorbs");
+      assert!(multipliers > 0, "{dims:?} level {level} has no multipliers");
+      assert!(ones > 0, "{dims:?} level {level} has no 1s");
+    }
+  }
+}
+
+#[test]
+fn big_boards_pan() {
+  // A 100x40 screen has room for a few columns and rows of normal cards, not
+  // 16.
+  let mut h = Harness::with_dims(1, (16, 16), Cell::from_xy(100, 40));
+  h.game.options.card_size = Some(CardSize::Normal);
+  h.tty.script([Event::Winch(Cell::from_xy(100, 40))]);
+  h.frame();
+  assert!(find_card(&h, 0).is_some());
+  assert!(find_card(&h, 255).is_none());
+
+  // Moving the selection to the far corner brings it into view, and it can be
+  // flipped with the mouse there.
+  h.select(255);
+  assert!(find_card(&h, 0).is_none());
+  let cell = find_card(&h, 255).unwrap();
+  h.tty.script([Event::Mouse {
+    button: Some(0),
+    cell,
+    action: MouseAction::Press,
+    mods: BitFlags::empty(),
+  }]);
+  h.settle();
+  assert!(h.game.cards[255].flipped);
+  assert!(h.tty.to_plain().contains("Showing cols 12-16, rows 11-16"));
+}
+
+#[test]
+fn cards_fit_the_screen() {
+  // The biggest cards that fit are picked, and drawn with all the same parts.
+  let sizes = [
+    ("compact_board.txt", Cell::from_xy(80, 24), "╭───╮"),
+    ("first_board.txt", Cell::from_xy(100, 40), "╭───────╮"),
+    ("large_board.txt", Cell::from_xy(160, 50), "╭────────────╮"),
+  ];
+  for (golden, viewport, corner) in sizes {
+    let mut h = Harness::with_dims(1, (5, 5), viewport);
+    let idx = h.cards(|v| v > 1)[0];
+    if golden != "first_board.txt" {
+      h.press(Key::Glyph('0'));
+      h.press(Key::Glyph('2'));
+      h.flip(idx);
+      assert_golden(golden, &h.tty.to_plain());
+    }
+    assert_eq!(h.tty.to_plain().matches(corner).count(), 34, "{golden}");
+  }
+
+  // Asking for a size overrides what fits; a board that doesn't fit pans.
+  let mut h = Harness::with_dims(1, (5, 5), Cell::from_xy(80, 24));
+  h.game.options.card_size = Some(CardSize::Large);
+  h.tty.script([Event::Winch(Cell::from_xy(80, 24))]);
+  h.frame();
+  assert!(h.tty.to_plain().contains("╭────────────╮"));
+  assert!(h.tty.to_plain().contains("Showing cols 1-2, rows 1-1"));
+}
+
+#[test]
+fn cards_stay_visible_mid_flip() {
+  for (viewport, columns) in [
+    (Cell::from_xy(80, 24), 1),
+    (Cell::from_xy(100, 40), 1),
+    (Cell::from_xy(160, 50), 2),
+  ] {
+    let mut h = Harness::with_dims(1, (5, 5), viewport);
+    let idx = h.cards(|v| v > 1)[0];
+    h.select(idx);
+    h.tty.script([key(Key::Enter)]);
+    h.frame();
+    while h.game.animation(Entity::Card(idx), Effect::Flip) != Some(4) {
+      h.frame_after(Duration::from_millis(1));
+    }
+
+    // Halfway through, only the middle of the card is left.
+    let frame = h.game.frame.as_ref().unwrap();
+    let (x, y) = viewport.xy();
+    let drawn = (0..x)
+      .filter(|&col| {
+        (0..y).any(|row| {
+          frame.target_at(Cell::from_xy(col, row))
+            == Some(gfx::Target::Card(idx))
+        })
+      })
+      .count();
+    assert_eq!(drawn, columns, "{viewport:?}");
+  }
+}
+
+#[test]
+fn other_layouts() {
+  // A tall terminal gets the controls under the board, rather than smaller
+  // cards.
+  let h = Harness::with_dims(1, (5, 5), Cell::from_xy(60, 50));
+  assert_golden("portrait_board.txt", &h.tty.to_plain());
+
+  let mut h = Harness::new(1);
+  h.game.options.hint_side = HintSide::TopLeft;
+  h.tty.script([Event::Winch(Cell::from_xy(100, 40))]);
+  h.frame();
+  assert_golden("top_left_board.txt", &h.tty.to_plain());
+
+  // The mouse still finds cards where they were moved to.
+  let idx = h.cards(|v| v > 1)[0];
+  let cell = find_card(&h, idx).unwrap();
+  h.tty.script([Event::Mouse {
+    button: Some(0),
+    cell,
+    action: MouseAction::Press,
+    mods: BitFlags::empty(),
+  }]);
+  h.settle();
+  assert!(h.game.cards[idx].flipped);
+}
+
+#[test]
+fn reveal_is_staggered() {
+  let mut h = Harness::new(1);
+  let stage =
+    |h: &Harness, idx| h.game.animation(Entity::Card(idx), Effect::Flip);
+  let last = h.game.cards.len() - 1;
+
+  // The board turns over in a wave, so the far corner hasn't started yet.
+  h.game.flip_all(true);
+  h.clock.advance(Duration::from_millis(100));
+  assert_eq!(stage(&h, 0), Some(3));
+  assert_eq!(stage(&h, last), Some(0));
+
+  h.settle();
+  assert!(h.game.cards.iter().all(|c| c.flipped));
+  assert_eq!(stage(&h, last), None);
+}
+
+#[test]
+fn boards_follow_the_seed() {
+  let board = |seed, level| {
+    let mut h = Harness::new(seed);
+    h.game.level = level;
+    h.game.generate_board();
+    h.game.cards.iter().map(|c| c.value).collect::<Vec<_>>()
+  };
+  for level in 1..=8 {
+    assert_eq!(board(1, level), board(1, level), "level {level}");
+    assert_ne!(board(1, level), board(2, level), "level {level}");
+  }
+}
+
+#[test]
+fn resumed_games_deal_the_same_boards() {
+  // Loses a few rounds, returning the boards dealt after each.
+  let next_boards = |h: &mut Harness| {
+    (0..3)
+      .map(|_| {
+        h.flip_last(h.cards(|v| v == 0)[0]);
+        h.press(Key::Glyph(' '));
+        h.game.cards.iter().map(|c| c.value).collect::<Vec<_>>()
+      })
+      .collect::<Vec<_>>()
+  };
+
+  // However many rounds in a game is saved, resuming it carries on as if it
+  // had never stopped.
+  let mut h = Harness::new(1);
+  next_boards(&mut h);
+  let mut resumed = Harness::new(2);
+  resumed.game.offer_resume(h.game.save());
+  resumed.game.accept_resume(true);
+  assert_eq!(next_boards(&mut resumed), next_boards(&mut h));
+}
+
+#[test]
+fn bank_keeps_coins() {
+  let mut h = Harness::new(1);
+  h.game.level = 4;
+  h.game.generate_board();
+  let multipliers = h.cards(|v| v > 1);
+  h.flip(multipliers[0]);
+  h.flip(multipliers[1]);
+  let coins = h.game.round_score;
+  assert!(coins > 1);
+
+  // Banking drops the level to the number of cards flipped, like losing, but
+  // the coins are kept.
+  h.press(Key::Glyph('b'));
+  h.end_round_with(Key::Enter);
+  assert!(matches!(
+    h.game.state,
+    State::GameOver {
+      new_level: 2,
+      outcome: Outcome::Quit,
+    }
+  ));
+  h.press(Key::Glyph(' '));
+  assert_eq!(h.game.level, 2);
+  assert_eq!(h.game.score, coins);
+
+  // Quitting the game works from the bank dialog too.
+  h.press(Key::Glyph('b'));
+  assert!(matches!(h.game.state, State::ConfirmQuit));
+  h.tty.script([key(Key::Glyph('q'))]);
+  assert!(!h.frame());
+}
+
+#[test]
+fn short_wins_keep_the_top_level() {
+  let mut h = Harness::new(1);
+  h.game.level = 8;
+  h.game.generate_board();
+  // Leave only two multipliers, so the round is won well short of a streak.
+  let multipliers = h.cards(|v| v > 1);
+  for &idx in &multipliers[2..] {
+    h.game.cards[idx].value = 1;
+  }
+  h.flip(multipliers[0]);
+  h.flip_last(multipliers[1]);
+  assert!(matches!(
+    h.game.state,
+    State::GameOver {
+      new_level: 8,
+      outcome: Outcome::Win,
+    }
+  ));
+  h.press(Key::Glyph(' '));
+  assert_eq!(h.game.level, 8);
+}
+
+#[test]
+fn themes_mark_hints_and_risk() {
+  let mut h = Harness::new(1);
+  let screen = h.tty.to_plain();
+  assert!(!screen.contains('●') && !screen.contains('▁'));
+
+  // Without color, hints and risky cards still stand out by their marks.
+  h.game.sheet = Theme::Builtin("monochrome").load().unwrap();
+  h.press(Key::Glyph('h'));
+  let screen = h.tty.to_plain();
+  for mark in ['●', '▲', '■', '◆', '★'] {
+    assert_eq!(screen.matches(mark).count(), 2, "{mark}");
+  }
+  assert!(screen.contains(['▁', '▃', '▅', '▇']));
+}
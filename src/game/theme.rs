@@ -0,0 +1,495 @@
+//! Themes, which pick the colors and line weights the game is drawn with.
+//!
+//! A theme is a TOML file that can set any field of the stylesheet; whatever
+//! it leaves out is taken from the default theme. A style is either a color
+//! for the text, or a table with any of `fg`, `bg`, `weight` (`normal`, `bold`
+//! or `light`), `underline`, `italic`, `reverse` and `strikethrough`. Colors
+//! are ANSI color names like `lt-cyan`, indices into the 256-color palette, or
+//! hex codes; `default` means the terminal's own color. Border weights are
+//! `normal`, `thick` or `doubled`. Marks, drawn on hints and on the heatmap,
+//! are single characters; by default there are none.
+//!
+//! ```toml
+//! card_style = "#2e8b57"
+//! card_weight = "normal"
+//! selected_style = { fg = "lt-cyan", weight = "bold" }
+//! voltorb_red = { fg = "lt-red", bg = "default", weight = "bold" }
+//! hint_colors = ["dk-red", "dk-green", "dk-yellow", "dk-blue", "dk-magenta"]
+//! ```
+
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use boxy as b;
+use serde::Deserialize;
+
+use crate::game::gfx::Stylesheet;
+use crate::game::Game;
+use crate::term::texel::Color;
+use crate::term::texel::Style;
+use crate::term::texel::Weight;
+use crate::term::Key;
+
+/// Themes that come with the game, as names and TOML sources.
+const BUILTIN: [(&str, &str); 4] = [
+  ("default", ""),
+  ("colorblind", include_str!("themes/colorblind.toml")),
+  ("high-contrast", include_str!("themes/high-contrast.toml")),
+  ("monochrome", include_str!("themes/monochrome.toml")),
+];
+
+/// Where to load a theme from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Theme {
+  /// One of the themes that come with the game, by name.
+  Builtin(&'static str),
+  /// A theme file.
+  File(PathBuf),
+}
+
+impl Theme {
+  /// Returns the directory that themes can be referred to by name from.
+  fn dir() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("voltorb").join("themes"))
+  }
+
+  /// Returns every theme there is to choose from: the built-in ones, and then
+  /// any in the themes directory.
+  pub fn available() -> Vec<Theme> {
+    let mut themes = BUILTIN.map(|(name, _)| Self::Builtin(name)).to_vec();
+
+    let mut files = Self::dir()
+      .and_then(|dir| fs::read_dir(dir).ok())
+      .into_iter()
+      .flatten()
+      .filter_map(|entry| Some(entry.ok()?.path()))
+      .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+      .collect::<Vec<_>>();
+    files.sort();
+    themes.extend(files.into_iter().map(Self::File));
+    themes
+  }
+
+  /// Returns a short name for this theme, for showing to the player.
+  pub fn name(&self) -> String {
+    match self {
+      Self::Builtin(name) => name.to_string(),
+      Self::File(path) => match path.file_stem() {
+        Some(stem) if path.parent() == Self::dir().as_deref() => {
+          stem.to_string_lossy().into_owned()
+        }
+        _ => path.display().to_string(),
+      },
+    }
+  }
+
+  /// Loads this theme, reading it from disk if need be.
+  pub fn load(&self) -> Result<Stylesheet, String> {
+    let (name, toml) = match self {
+      Self::Builtin(name) => {
+        let (_, toml) = BUILTIN.iter().find(|(n, _)| n == name).unwrap();
+        (name.to_string(), toml.to_string())
+      }
+      Self::File(path) => {
+        let name = path.display().to_string();
+        match fs::read_to_string(path) {
+          Ok(toml) => (name, toml),
+          Err(e) => return Err(format!("{name}: {e}")),
+        }
+      }
+    };
+
+    parse(&toml).map_err(|e| format!("{name}: {e}"))
+  }
+}
+
+/// Parses the contents of a theme file.
+fn parse(toml: &str) -> Result<Stylesheet, String> {
+  let file = toml::from_str::<File>(toml).map_err(|e| e.to_string())?;
+  file.apply(Stylesheet::default())
+}
+
+impl Default for Theme {
+  /// Returns the default theme, which is monochrome if the user asked for no
+  /// color; see <https://no-color.org>.
+  fn default() -> Self {
+    if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+      Self::Builtin("monochrome")
+    } else {
+      Self::Builtin("default")
+    }
+  }
+}
+
+impl FromStr for Theme {
+  type Err = String;
+
+  /// Parses a theme: the name of a built-in theme, the name of a file in the
+  /// themes directory (without `.toml`), or a path to a file.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if let Some(&(name, _)) = BUILTIN.iter().find(|(name, _)| *name == s) {
+      return Ok(Self::Builtin(name));
+    }
+    if s.contains(std::path::MAIN_SEPARATOR) || s.ends_with(".toml") {
+      return Ok(Self::File(s.into()));
+    }
+    match Self::dir() {
+      Some(dir) => Ok(Self::File(dir.join(format!("{s}.toml")))),
+      None => Err(format!("unknown theme `{s}`")),
+    }
+  }
+}
+
+/// The state of the in-game settings screen.
+pub(super) struct Settings {
+  /// The themes to choose from.
+  pub themes: Vec<Theme>,
+  /// The index of the theme the cursor is on.
+  pub cursor: usize,
+}
+
+impl Game {
+  /// Opens the settings screen, with the cursor on the current theme.
+  pub(super) fn open_settings(&mut self) {
+    let themes = Theme::available();
+    let cursor = themes
+      .iter()
+      .position(|t| *t == self.options.theme)
+      .unwrap_or(0);
+    self.settings = Some(Settings { themes, cursor });
+  }
+
+  /// Handles a key press while the settings screen is open.
+  pub(super) fn settings_key(&mut self, key: Key) {
+    let Some(settings) = &mut self.settings else {
+      return;
+    };
+    let len = settings.themes.len();
+    match key {
+      Key::Up => settings.cursor = (settings.cursor + len - 1) % len,
+      Key::Down => settings.cursor = (settings.cursor + 1) % len,
+      Key::Enter => {
+        let theme = settings.themes[settings.cursor].clone();
+        match theme.load() {
+          Ok(sheet) => {
+            self.sheet = sheet;
+            self.options.theme = theme;
+          }
+          Err(e) => self.notice = Some(e),
+        }
+      }
+      Key::Esc | Key::Glyph('o' | 'O' | 'q' | 'Q') => self.settings = None,
+      _ => {}
+    }
+  }
+
+  /// Loads the theme again, so that changes to a theme file can be seen
+  /// without restarting.
+  pub(super) fn reload_theme(&mut self) {
+    self.notice = Some(match self.options.theme.load() {
+      Ok(sheet) => {
+        self.sheet = sheet;
+        "Theme reloaded.".to_string()
+      }
+      Err(e) => e,
+    });
+  }
+}
+
+/// The contents of a theme file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+  card_style: Option<StyleDef>,
+  card_weight: Option<BorderWeight>,
+  selected_style: Option<StyleDef>,
+  selected_weight: Option<BorderWeight>,
+  number_style: Option<StyleDef>,
+  number_weight: Option<BorderWeight>,
+  voltorb_red: Option<StyleDef>,
+  voltorb_wht: Option<StyleDef>,
+  coin_style: Option<StyleDef>,
+  memo_style: Option<StyleDef>,
+  hint_colors: Option<Vec<StyleDef>>,
+  hint_weights: Option<Vec<BorderWeight>>,
+  hint_marks: Option<Vec<String>>,
+  risk_styles: Option<Vec<StyleDef>>,
+  risk_marks: Option<Vec<String>>,
+  safe_style: Option<StyleDef>,
+  doomed_style: Option<StyleDef>,
+}
+
+impl File {
+  /// Overrides the parts of `sheet` that this file sets.
+  fn apply(self, mut sheet: Stylesheet) -> Result<Stylesheet, String> {
+    let styles = [
+      (self.card_style, &mut sheet.card_style),
+      (self.selected_style, &mut sheet.selected_style),
+      (self.number_style, &mut sheet.number_style),
+      (self.voltorb_red, &mut sheet.voltorb_red),
+      (self.voltorb_wht, &mut sheet.voltorb_wht),
+      (self.coin_style, &mut sheet.coin_style),
+      (self.memo_style, &mut sheet.memo_style),
+      (self.safe_style, &mut sheet.safe_style),
+      (self.doomed_style, &mut sheet.doomed_style),
+    ];
+    for (def, style) in styles {
+      if let Some(def) = def {
+        *style = def.into_style()?;
+      }
+    }
+
+    let weights = [
+      (self.card_weight, &mut sheet.card_weight),
+      (self.selected_weight, &mut sheet.selected_weight),
+      (self.number_weight, &mut sheet.number_weight),
+    ];
+    for (weight, border) in weights {
+      if let Some(weight) = weight {
+        *border = weight.into();
+      }
+    }
+
+    if let Some(weights) = self.hint_weights {
+      if weights.is_empty() {
+        return Err("`hint_weights` needs at least one weight".to_string());
+      }
+      sheet.hint_weights = weights.into_iter().map(Into::into).collect();
+    }
+
+    let lists = [
+      ("hint_colors", self.hint_colors, &mut sheet.hint_colors),
+      ("risk_styles", self.risk_styles, &mut sheet.risk_styles),
+    ];
+    for (name, defs, styles) in lists {
+      let Some(defs) = defs else { continue };
+      if defs.is_empty() {
+        return Err(format!("`{name}` needs at least one style"));
+      }
+      *styles = defs
+        .into_iter()
+        .map(StyleDef::into_style)
+        .collect::<Result<_, _>>()?;
+    }
+
+    let marks = [
+      ("hint_marks", self.hint_marks, &mut sheet.hint_marks),
+      ("risk_marks", self.risk_marks, &mut sheet.risk_marks),
+    ];
+    for (name, defs, marks) in marks {
+      let Some(defs) = defs else { continue };
+      *marks = defs
+        .iter()
+        .map(|def| {
+          let mut chars = def.chars();
+          match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => {
+              Err(format!("`{name}` must be single characters, not `{def}`"))
+            }
+          }
+        })
+        .collect::<Result<_, _>>()?;
+    }
+
+    Ok(sheet)
+  }
+}
+
+/// A style, as written in a theme file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StyleDef {
+  /// Just a foreground color.
+  Fg(ColorDef),
+  Full(StyleTable),
+}
+
+/// A style written out as a table.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleTable {
+  fg: Option<ColorDef>,
+  bg: Option<ColorDef>,
+  weight: Option<TextWeight>,
+  #[serde(default)]
+  underline: bool,
+  #[serde(default)]
+  italic: bool,
+  #[serde(default)]
+  reverse: bool,
+  #[serde(default)]
+  strikethrough: bool,
+}
+
+impl StyleDef {
+  fn into_style(self) -> Result<Style, String> {
+    let color = |c: Option<ColorDef>| match c {
+      None => Ok(None),
+      Some(ColorDef::Index(i)) => Ok(Some(Color::Indexed(i))),
+      Some(ColorDef::Name(name)) if name == "default" => Ok(None),
+      Some(ColorDef::Name(name)) => name.parse::<Color>().map(Some),
+    };
+
+    match self {
+      Self::Fg(fg) => Ok(Style::new().with_fg(color(Some(fg))?)),
+      Self::Full(StyleTable {
+        fg,
+        bg,
+        weight,
+        underline,
+        italic,
+        reverse,
+        strikethrough,
+      }) => Ok(
+        Style::new()
+          .with_fg(color(fg)?)
+          .with_bg(color(bg)?)
+          .with_weight(weight.map_or(Weight::Normal, Weight::from))
+          .with_underline(underline)
+          .with_italic(italic)
+          .with_reverse(reverse)
+          .with_strikethrough(strikethrough),
+      ),
+    }
+  }
+}
+
+/// A color, as written in a theme file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorDef {
+  /// A name or hex code, or `default`.
+  Name(String),
+  /// An index into the 256-color palette.
+  Index(u8),
+}
+
+/// A text weight, as written in a theme file.
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TextWeight {
+  Normal,
+  Bold,
+  Light,
+}
+
+impl From<TextWeight> for Weight {
+  fn from(w: TextWeight) -> Self {
+    match w {
+      TextWeight::Normal => Self::Normal,
+      TextWeight::Bold => Self::Bold,
+      TextWeight::Light => Self::Light,
+    }
+  }
+}
+
+/// A border weight, as written in a theme file.
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum BorderWeight {
+  Normal,
+  Thick,
+  Doubled,
+}
+
+impl From<BorderWeight> for b::Weight {
+  fn from(w: BorderWeight) -> Self {
+    match w {
+      BorderWeight::Normal => Self::Normal,
+      BorderWeight::Thick => Self::Thick,
+      BorderWeight::Doubled => Self::Doubled,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn valid_file() {
+    let sheet = parse(
+      r##"
+      card_style = "#2e8b57"
+      card_weight = "thick"
+      selected_style = { fg = "lt-cyan", bg = 17, weight = "bold", italic = true }
+      voltorb_red = { fg = "default", underline = true }
+      hint_colors = ["dk-red", 200]
+      hint_weights = ["doubled"]
+      risk_marks = ["a", "β"]
+      "##,
+    )
+    .unwrap();
+    assert_eq!(sheet.card_style, Color::Rgb(0x2e, 0x8b, 0x57).fg());
+    assert_eq!(sheet.card_weight, b::Weight::Thick);
+    assert_eq!(
+      sheet.selected_style,
+      Color::LtCyan
+        .fg()
+        .with_bg(Color::Indexed(17))
+        .with_weight(Weight::Bold)
+        .with_italic(true),
+    );
+    assert_eq!(sheet.voltorb_red, Style::new().with_underline(true));
+    assert_eq!(
+      sheet.hint_colors,
+      [Color::DkRed.fg(), Color::Indexed(200).fg()]
+    );
+    assert_eq!(sheet.hint_weights, [b::Weight::Doubled]);
+    assert_eq!(sheet.risk_marks, ['a', 'β']);
+
+    // Everything else comes from the default theme.
+    let default = Stylesheet::default();
+    assert_eq!(sheet.coin_style, default.coin_style);
+    assert_eq!(sheet.risk_styles, default.risk_styles);
+    assert_eq!(sheet.selected_weight, default.selected_weight);
+  }
+
+  #[test]
+  fn invalid_files() {
+    let cases = [
+      ("card_colour = \"lt-red\"", "unknown field `card_colour`"),
+      (
+        "card_style = { fg = \"lt-red\", bold = true }",
+        "card_style",
+      ),
+      ("card_style = \"lt-purple\"", "invalid color `lt-purple`"),
+      ("card_style = \"#12345\"", "invalid color `#12345`"),
+      ("card_weight = \"heavy\"", "unknown variant `heavy`"),
+      ("hint_colors = []", "`hint_colors` needs at least one style"),
+      (
+        "hint_weights = []",
+        "`hint_weights` needs at least one weight",
+      ),
+      (
+        "hint_marks = [\"*\", \"ab\"]",
+        "`hint_marks` must be single characters, not `ab`",
+      ),
+      (
+        "risk_marks = [\"\"]",
+        "`risk_marks` must be single characters",
+      ),
+    ];
+    for (toml, error) in cases {
+      let Err(e) = parse(toml) else {
+        panic!("{toml} parsed");
+      };
+      assert!(e.contains(error), "{toml}: {e}");
+    }
+  }
+
+  #[test]
+  fn builtin_themes() {
+    for (name, _) in BUILTIN {
+      let sheet = Theme::Builtin(name).load().unwrap();
+      // All but the default tell hints and risk apart by more than color.
+      if name != "default" {
+        assert!(sheet.hint_weights.len() > 1, "{name}");
+        assert!(!sheet.hint_marks.is_empty(), "{name}");
+        assert!(!sheet.risk_marks.is_empty(), "{name}");
+      }
+    }
+  }
+}
@@ -15,73 +15,206 @@ pub mod term;
 /// <https://youtu.be/gRXcyH1JdCI>
 #[derive(FromArgs)]
 struct Opts {
-  /// number of columns for the game board (5 to 8)
+  /// number of columns for the game board (3 to 16)
   #[argh(option, short = 'c', default = "5")]
   columns: u32,
-  /// number of rows for the game board (5 to 8)
+  /// number of rows for the game board (3 to 16)
   #[argh(option, short = 'r', default = "5")]
   rows: u32,
   /// maximum card number (3 to 9)
   #[argh(option, short = 'm', default = "3")]
   max_card: u8,
-  /// frames-per-second to run the game at
+  /// seed for the first board; a board can be reproduced from the seed shown
+  /// in the scoreboard, given the same level and board options
+  #[argh(option, short = 's')]
+  seed: Option<u64>,
+  /// how boards are generated: formula, or hgss for the HeartGold/SoulSilver
+  /// card counts (5x5 boards with max card 3 only; cards are placed freely,
+  /// without HGSS's limit on multipliers in Voltorb-free lines)
+  #[argh(option, short = 'g', default = "game::Generator::Formula")]
+  generator: game::Generator,
+  /// theme to draw the game with: default, colorblind, high-contrast,
+  /// monochrome (the default if NO_COLOR is set), the name of a file in the
+  /// themes config directory, or a path to a TOML file
+  #[argh(option, short = 't', default = "game::Theme::default()")]
+  theme: game::Theme,
+  /// how big to draw cards: compact, normal, or large; by default, the
+  /// biggest that fits the terminal
+  #[argh(option, short = 'z')]
+  card_size: Option<game::CardSize>,
+  /// where to put the controls: landscape (beside the board) or portrait
+  /// (under it); by default, whichever fits the biggest cards
+  #[argh(option, short = 'l')]
+  layout: Option<game::Layout>,
+  /// which sides of the board the hints go on: bottom-right or top-left
+  #[argh(option, default = "game::HintSide::BottomRight")]
+  hints: game::HintSide,
+  /// frames-per-second to animate the game at; when nothing is moving, the
+  /// game only wakes up for input
   #[argh(option, short = 'f', default = "30")]
   fps: u32,
+
+  #[argh(subcommand)]
+  command: Option<Command>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+  Simulate(Simulate),
+  Stats(Stats),
+}
+
+/// Play without a terminal and report how a strategy fares.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "simulate")]
+struct Simulate {
+  /// number of rounds to play
+  #[argh(option, short = 'n', default = "1000")]
+  rounds: usize,
+  /// how the bot picks cards: random or greedy-safest
+  #[argh(option, default = "game::Strategy::GreedySafest")]
+  strategy: game::Strategy,
+}
+
+/// Show statistics for the rounds you've played, per board configuration.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "stats")]
+struct Stats {
+  /// output format: table, json, or csv
+  #[argh(option, default = "game::Format::Table")]
+  format: game::Format,
+  /// list every round instead of summarizing them
+  #[argh(switch)]
+  rounds: bool,
 }
 
 fn main() {
   let opts: Opts = argh::from_env();
 
-  if !(5..=8).contains(&opts.columns) {
-    eprintln!("error: --columns must be between 5 and 8");
+  if !(3..=16).contains(&opts.columns) {
+    eprintln!("error: --columns must be between 3 and 16");
     exit(1)
   }
-  if !(5..=8).contains(&opts.rows) {
-    eprintln!("error: --rows must be between 5 and 8");
+  if !(3..=16).contains(&opts.rows) {
+    eprintln!("error: --rows must be between 3 and 16");
     exit(1)
   }
   if !(3..=9).contains(&opts.max_card) {
     eprintln!("error: --max-card must be between 3 and 9");
     exit(1)
   }
+  if opts.generator == game::Generator::Hgss
+    && (opts.columns, opts.rows, opts.max_card) != (5, 5, 3)
+  {
+    eprintln!("error: --generator hgss requires a 5x5 board with max card 3");
+    exit(1)
+  }
   if !(15..=120).contains(&opts.fps) {
     eprintln!("error: --fps must be between 15 and 120");
     exit(1)
   }
+  if let Err(e) = opts.theme.load() {
+    eprintln!("error: could not load theme {e}");
+    exit(1)
+  }
 
-  let mut tty = term::AnsiTty::default();
+  let options = game::Options {
+    board_dims: (opts.columns, opts.rows),
+    max_card_value: opts.max_card,
+    seed: opts.seed,
+    generator: opts.generator,
+    record_rounds: true,
+    theme: opts.theme,
+    card_size: opts.card_size,
+    layout: opts.layout,
+    hint_side: opts.hints,
+    enable_debugging: cfg!(debug_assertions)
+      && std::env::var("VOLTORB_DEBUG").is_ok(),
+  };
+
+  match opts.command {
+    Some(Command::Simulate(sim)) => {
+      print!("{}", game::simulate(options, sim.strategy, sim.rounds));
+      return;
+    }
+    Some(Command::Stats(stats)) => {
+      match game::report(stats.format, stats.rounds) {
+        Ok(report) => print!("{report}"),
+        Err(e) => {
+          eprintln!("error: could not read stats: {e}");
+          exit(1);
+        }
+      }
+      return;
+    }
+    None => {}
+  }
+
+  let mut game = game::Game::new(options);
+  match game::Save::read() {
+    Ok(Some(save)) => game.offer_resume(save),
+    Ok(None) => {}
+    Err(e) => eprintln!("warning: ignoring saved game: {e}"),
+  }
+
+  let mut tty = term::AnsiTty::new(
+    true,
+    term::texel::Palette::detect(),
+    term::AnsiTty::detect_synchronized_output(),
+  );
   tty.install_panic_hook();
+  let autosave = game::Autosave::default();
+  autosave.install_panic_hook();
 
   let result = term::with_tty(&mut tty, |tty| {
-    let mut game = game::Game::new(game::Options {
-      board_dims: (opts.columns, opts.rows),
-      max_card_value: opts.max_card,
-      enable_debugging: cfg!(debug_assertions)
-        && std::env::var("VOLTORB_DEBUG").is_ok(),
-    });
-
     let mut canvas = term::Canvas::new(tty.viewport()?);
+    game.resize(canvas.viewport());
 
-    let mut event = None;
-    loop {
+    let frame = Duration::from_secs_f64(1.0 / opts.fps as f64);
+    let mut events = Vec::new();
+    'game: loop {
       let frame_timer = Instant::now();
-      if let Some(term::Event::Winch(vp)) = event {
-        canvas.winch(vp);
-        event = None;
+
+      // An idle game ignores empty frames, so only bother with it when
+      // something happened.
+      if events.is_empty() && !game.is_idle() {
+        events.push(None);
       }
-      if !game.interact(event) {
-        break;
+      for event in events.drain(..) {
+        if let Some(term::Event::Winch(vp)) = event {
+          canvas.winch(vp);
+          game.resize(vp);
+        } else if !game.interact(event) {
+          break 'game;
+        }
+      }
+      if game.needs_render() {
+        autosave.update(&game);
+        let bytes = canvas.render(game.render(canvas.viewport()), tty)?;
+        game.frame_sent(bytes);
       }
-      canvas.render(game.render(canvas.viewport()), tty)?;
 
-      let timeout = Duration::from_secs_f64(1.0 / opts.fps as f64)
-        .saturating_sub(frame_timer.elapsed());
-      event = tty.poll(Some(timeout))?;
+      // With nothing to animate, sleep until the player does something. Then
+      // take everything that has already arrived, so that a burst of input
+      // isn't spread out over several frames.
+      let mut timeout = if game.is_idle() {
+        None
+      } else {
+        Some(frame.saturating_sub(frame_timer.elapsed()))
+      };
+      while let Some(event) = tty.poll(timeout)? {
+        events.push(Some(event));
+        timeout = Some(Duration::ZERO);
+      }
     }
 
     tty.fini()
   });
 
+  if let Err(e) = game.save().write() {
+    eprintln!("warning: could not save game: {e}");
+  }
   if let Err(e) = result {
     eprintln!("error: {e}");
     exit(1);
@@ -4,6 +4,7 @@ use std::borrow::Cow;
 use std::io;
 use std::iter;
 use std::mem;
+use std::ops::Range;
 
 use crate::term::texel::Texel;
 use crate::term::Cell;
@@ -17,6 +18,9 @@ pub struct Layer<'a> {
   pub stride: usize,
   /// The texel data to render.
   pub data: Cow<'a, [Texel]>,
+  /// An identifier to report when hit-testing finds this layer; `None` for
+  /// layers that aren't interesting to point at.
+  pub id: Option<usize>,
 }
 
 impl Layer<'_> {
@@ -29,6 +33,34 @@ impl Layer<'_> {
     )
   }
 
+  /// Returns the texel this layer would draw at `cell`, if `cell` falls within
+  /// it.
+  pub fn texel_at(&self, cell: Cell) -> Option<Texel> {
+    let col = cell.col().checked_sub(self.origin.col())?;
+    let row = cell.row().checked_sub(self.origin.row())?;
+    if col >= self.stride {
+      return None;
+    }
+    self.data.get(row * self.stride + col).copied()
+  }
+
+  /// Finds the layer that owns the topmost visible texel at `cell`, and
+  /// returns its id.
+  ///
+  /// Layers are stacked the same way [`Canvas::render()`] stacks them, and
+  /// transparent texels don't count, so this reports what the player actually
+  /// sees at `cell`.
+  pub fn hit_test<'a>(
+    layers: impl IntoIterator<Item = &'a Layer<'a>>,
+    cell: Cell,
+  ) -> Option<usize> {
+    layers
+      .into_iter()
+      .filter(|l| l.texel_at(cell).is_some_and(|tx| tx.glyph().is_some()))
+      .last()?
+      .id
+  }
+
   /// Computes the bounding box for all layers in `layers`, inclusive.
   pub fn bounding_box<'a>(
     layers: impl IntoIterator<Item = &'a Layer<'a>>,
@@ -88,8 +120,9 @@ impl Canvas {
     let new_cap = x * y;
     if new_cap < self.buffer.capacity() / 2 {
       self.buffer.shrink_to(new_cap);
-    } else if let Some(growth) = new_cap.checked_sub(self.buffer.capacity()) {
-      self.buffer.reserve(growth);
+    } else {
+      // The buffer is empty, so this reserves room for all of `new_cap`.
+      self.buffer.reserve(new_cap);
     }
   }
 
@@ -98,11 +131,14 @@ impl Canvas {
   /// This function is not intended to be called on multiple different `tty`s,
   /// since it remembers what was written the *last* time this function was
   /// called.
+  ///
+  /// Everything is written as a single frame; see [`Tty::begin_frame()`].
+  /// Returns the number of bytes the frame took to send, if `tty` knows.
   pub fn render<'a>(
     &mut self,
     layers: impl IntoIterator<Item = Layer<'a>>,
     tty: &mut dyn Tty,
-  ) -> io::Result<()> {
+  ) -> io::Result<usize> {
     let mut side_buffer;
     let (buffer, old) = if self.buffer.is_empty() {
       (&mut self.buffer, None)
@@ -111,9 +147,9 @@ impl Canvas {
       (&mut side_buffer, Some(&mut self.buffer))
     };
 
-    buffer.extend(iter::repeat(Texel::empty()).take(buffer.capacity()));
+    let (x, y) = self.viewport.xy();
+    buffer.extend(iter::repeat_n(Texel::empty(), x * y));
     for l in layers {
-      let (x, _) = self.viewport.xy();
       let (ox, oy) = l.origin.xy();
       if ox >= x {
         continue;
@@ -134,27 +170,13 @@ impl Canvas {
       }
     }
 
+    tty.begin_frame()?;
     for (i, line) in buffer.chunks(self.viewport.col()).enumerate() {
       if let Some(old) = &old {
-        let mut last_boundary = 0;
-        let mut same_at_last_boundary = true;
-        for (j, &tx) in line.iter().enumerate() {
-          let old = old[i * self.viewport.col() + j];
-          let same = old == tx;
-          if same == same_at_last_boundary {
-            continue;
-          }
-          same_at_last_boundary = same;
-
-          if same {
-            let start = Cell::from_xy(last_boundary, i);
-            tty.write(start, &line[last_boundary..j])?;
-          }
-          last_boundary = j;
-        }
-        if same_at_last_boundary {
-          let start = Cell::from_xy(last_boundary, i);
-          tty.write(start, &line[last_boundary..])?;
+        let old = &old[i * self.viewport.col()..];
+        for run in changed_runs(line, old) {
+          let start = Cell::from_xy(run.start, i);
+          tty.write(start, &line[run])?;
         }
       } else {
         let start = Cell::from_xy(0, i);
@@ -162,7 +184,136 @@ impl Canvas {
       }
     }
 
+    let bytes = tty.end_frame()?;
+
     self.buffer = mem::take(buffer);
-    Ok(())
+    Ok(bytes)
+  }
+}
+
+/// A rough cost, in bytes, of changing the pen's style.
+const STYLE_COST: usize = 6;
+
+/// Finds the runs of texels in `line` that differ from `old`, and so need to be
+/// written.
+///
+/// Two runs separated by a short gap of unchanged texels are merged when
+/// writing the gap over again is estimated to be cheaper than moving the
+/// cursor past it.
+fn changed_runs(line: &[Texel], old: &[Texel]) -> Vec<Range<usize>> {
+  let mut runs = Vec::<Range<usize>>::new();
+  for (j, (new, old)) in Iterator::zip(line.iter(), old).enumerate() {
+    if new == old {
+      continue;
+    }
+    match runs.last_mut() {
+      Some(run) if run.end == j => run.end += 1,
+      Some(run)
+        if rewrite_cost(&line[run.end - 1..=j])
+          <= skip_cost(line, run.end, j) =>
+      {
+        run.end = j + 1
+      }
+      _ => runs.push(j..j + 1),
+    }
+  }
+  runs
+}
+
+/// Estimates the cost of writing the gap between the first and last texels of
+/// `span`, which are written either way.
+fn rewrite_cost(span: &[Texel]) -> usize {
+  let glyphs = span[1..span.len() - 1]
+    .iter()
+    .map(|tx| tx.glyph().unwrap_or(' ').len_utf8())
+    .sum::<usize>();
+  let style_changes = span
+    .windows(2)
+    .filter(|w| w[0].style() != w[1].style())
+    .count();
+  glyphs + style_changes * STYLE_COST
+}
+
+/// Estimates the cost of moving the cursor from `line[end]` to `line[next]`
+/// without writing anything in between.
+fn skip_cost(line: &[Texel], end: usize, next: usize) -> usize {
+  // A relative move, `CSI n C`.
+  let gap = next - end;
+  let digits = gap.to_string().len();
+  let style_change = line[end - 1].style() != line[next].style();
+  3 + digits + style_change as usize * STYLE_COST
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::term::texel::Style;
+  use crate::term::MockTty;
+
+  #[test]
+  fn short_gaps_are_rewritten() {
+    let line = |s| Style::new().texels_from_str(s).collect::<Vec<_>>();
+    let old = line("aaaaaaaaaaaaaaaaaaaa");
+    assert_eq!(
+      changed_runs(&line("abaabaaaaaaaaaaaaaab"), &old),
+      [1..5, 19..20]
+    );
+    assert_eq!(changed_runs(&old, &old), []);
+  }
+
+  #[test]
+  fn changes_at_the_end_of_a_line_are_redrawn() {
+    let viewport = Cell::from_xy(6, 2);
+    let frame = |s: &str| Layer {
+      origin: Cell::from_xy(0, 0),
+      stride: 6,
+      data: Style::new().texels_from_str(s).collect::<Vec<_>>().into(),
+      id: None,
+    };
+    let mut canvas = Canvas::new(viewport);
+    let mut tty = MockTty::new(viewport);
+    canvas.render([frame("aaaaaabbbbbb")], &mut tty).unwrap();
+
+    // A run that reaches the end of a line used to be left stale.
+    canvas.render([frame("aaaazzbzbbbz")], &mut tty).unwrap();
+    assert_eq!(tty.to_plain(), "aaaazz\nbzbbbz\n");
+  }
+
+  #[test]
+  fn hit_test_sees_through_gaps() {
+    // Spaces are drawn as transparent texels.
+    let layer = |x, y, stride, s: &str, id| Layer {
+      origin: Cell::from_xy(x, y),
+      stride,
+      data: Style::new()
+        .texels_from_str(s)
+        .map(|tx| {
+          if tx.glyph() == Some(' ') {
+            Texel::empty()
+          } else {
+            tx
+          }
+        })
+        .collect::<Vec<_>>()
+        .into(),
+      id,
+    };
+    // A box with a hole in the middle, under a bar with a gap in the middle.
+    let layers = [
+      layer(0, 0, 3, "#########", Some(0)),
+      layer(1, 1, 1, " ", Some(1)),
+      layer(0, 1, 3, "= =", Some(2)),
+      layer(2, 2, 1, "!", None),
+    ];
+    let hit = |x, y| Layer::hit_test(&layers, Cell::from_xy(x, y));
+
+    assert_eq!(hit(1, 0), Some(0));
+    assert_eq!(hit(0, 1), Some(2));
+    assert_eq!(hit(2, 1), Some(2));
+    // Neither the transparent layer nor the gap above it hide the box.
+    assert_eq!(hit(1, 1), Some(0));
+    // The topmost layer counts, even if there's nothing to report for it.
+    assert_eq!(hit(2, 2), None);
+    assert_eq!(hit(5, 5), None);
   }
 }
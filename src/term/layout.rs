@@ -0,0 +1,241 @@
+//! A tiny layout engine, for working out where [`Layer`]s go.
+//!
+//! A layout is a tree of [`Node`]s: leaves are boxes of a known size, and
+//! everything else is a row or column of other nodes. Placing the tree yields
+//! the origin of every leaf, ready to be used as a layer's origin.
+//!
+//! [`Layer`]: crate::term::Layer
+
+use crate::term::Cell;
+
+/// Where to put a node within the space its row or column gives it, across
+/// the direction the row or column runs in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Align {
+  /// Along the top of a row, or the left of a column.
+  Start,
+  /// In the middle, rounding towards the start.
+  Center,
+  /// Along the bottom of a row, or the right of a column.
+  End,
+}
+
+/// A box in a layout, tagged with `T` at the leaves.
+#[derive(Clone, Debug)]
+pub struct Node<T> {
+  kind: Kind<T>,
+  /// Blank space above and to the left of the contents.
+  upper: Cell,
+  /// Blank space below and to the right of the contents.
+  lower: Cell,
+}
+
+#[derive(Clone, Debug)]
+enum Kind<T> {
+  Leaf(T, Cell),
+  Row(Stack<T>),
+  Column(Stack<T>),
+}
+
+#[derive(Clone, Debug)]
+struct Stack<T> {
+  children: Vec<Node<T>>,
+  /// Blank space between each child.
+  gap: usize,
+  align: Align,
+}
+
+impl<T> Node<T> {
+  /// Creates a box of the given size.
+  pub fn leaf(tag: T, size: Cell) -> Self {
+    Self::new(Kind::Leaf(tag, size))
+  }
+
+  /// Creates a row of boxes, from left to right.
+  pub fn row(children: impl IntoIterator<Item = Node<T>>) -> Self {
+    Self::new(Kind::Row(Stack::new(children)))
+  }
+
+  /// Creates a column of boxes, from top to bottom.
+  pub fn column(children: impl IntoIterator<Item = Node<T>>) -> Self {
+    Self::new(Kind::Column(Stack::new(children)))
+  }
+
+  fn new(kind: Kind<T>) -> Self {
+    Self {
+      kind,
+      upper: Cell::from_xy(0, 0),
+      lower: Cell::from_xy(0, 0),
+    }
+  }
+
+  /// Puts `gap` blank cells between the children of a row or column.
+  ///
+  /// Does nothing to a leaf.
+  pub fn gap(mut self, gap: usize) -> Self {
+    if let Kind::Row(stack) | Kind::Column(stack) = &mut self.kind {
+      stack.gap = gap;
+    }
+    self
+  }
+
+  /// Sets how the children of a row or column line up with each other.
+  ///
+  /// Does nothing to a leaf.
+  pub fn align(mut self, align: Align) -> Self {
+    if let Kind::Row(stack) | Kind::Column(stack) = &mut self.kind {
+      stack.align = align;
+    }
+    self
+  }
+
+  /// Adds blank space around this box: `upper` above and to the left, and
+  /// `lower` below and to the right.
+  pub fn pad(mut self, upper: Cell, lower: Cell) -> Self {
+    self.upper = upper;
+    self.lower = lower;
+    self
+  }
+
+  /// Returns how much space this box takes up, including its padding.
+  pub fn size(&self) -> Cell {
+    let (x, y) = match &self.kind {
+      Kind::Leaf(_, size) => size.xy(),
+      Kind::Row(stack) => stack.measure(|c| c.size().xy()),
+      Kind::Column(stack) => {
+        let (main, cross) = stack.measure(|c| {
+          let (x, y) = c.size().xy();
+          (y, x)
+        });
+        (cross, main)
+      }
+    };
+    Cell::from_xy(
+      self.upper.col() + x + self.lower.col(),
+      self.upper.row() + y + self.lower.row(),
+    )
+  }
+
+  /// Places this box with its upper-left corner at `origin`, returning the
+  /// origin of every leaf, in the order they were added.
+  pub fn place(self, origin: Cell) -> Vec<(T, Cell)> {
+    let mut out = Vec::new();
+    self.place_into(origin, &mut out);
+    out
+  }
+
+  fn place_into(self, origin: Cell, out: &mut Vec<(T, Cell)>) {
+    let size = self.size();
+    let (x, y) = (
+      origin.col() + self.upper.col(),
+      origin.row() + self.upper.row(),
+    );
+    let inner = Cell::from_xy(
+      size.col() - self.upper.col() - self.lower.col(),
+      size.row() - self.upper.row() - self.lower.row(),
+    );
+    match self.kind {
+      Kind::Leaf(tag, _) => out.push((tag, Cell::from_xy(x, y))),
+      Kind::Row(stack) => {
+        let mut main = x;
+        for child in stack.children {
+          let (w, h) = child.size().xy();
+          let cross = y + stack.align.offset(h, inner.row());
+          child.place_into(Cell::from_xy(main, cross), out);
+          main += w + stack.gap;
+        }
+      }
+      Kind::Column(stack) => {
+        let mut main = y;
+        for child in stack.children {
+          let (w, h) = child.size().xy();
+          let cross = x + stack.align.offset(w, inner.col());
+          child.place_into(Cell::from_xy(cross, main), out);
+          main += h + stack.gap;
+        }
+      }
+    }
+  }
+}
+
+impl<T> Stack<T> {
+  fn new(children: impl IntoIterator<Item = Node<T>>) -> Self {
+    Self {
+      children: children.into_iter().collect(),
+      gap: 0,
+      align: Align::Start,
+    }
+  }
+
+  /// Returns the total length of the children along the direction the stack
+  /// runs in, and the length of the longest child across it, given a
+  /// function that returns those two lengths for a child.
+  fn measure(
+    &self,
+    lengths: impl Fn(&Node<T>) -> (usize, usize),
+  ) -> (usize, usize) {
+    let gaps = self.gap * self.children.len().saturating_sub(1);
+    self
+      .children
+      .iter()
+      .map(lengths)
+      .fold((gaps, 0), |(main, cross), (m, c)| (main + m, cross.max(c)))
+  }
+}
+
+impl Align {
+  /// Returns how far into `space` something of length `len` goes.
+  fn offset(self, len: usize, space: usize) -> usize {
+    let slack = space.saturating_sub(len);
+    match self {
+      Self::Start => 0,
+      Self::Center => slack / 2,
+      Self::End => slack,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn leaf(tag: char, x: usize, y: usize) -> Node<char> {
+    Node::leaf(tag, Cell::from_xy(x, y))
+  }
+
+  #[test]
+  fn rows_and_columns() {
+    let tree = Node::column([
+      Node::row([leaf('a', 2, 1), leaf('b', 3, 2)]).gap(1),
+      leaf('c', 4, 1).pad(Cell::from_xy(1, 1), Cell::from_xy(0, 0)),
+    ])
+    .gap(2);
+    assert_eq!(tree.size(), Cell::from_xy(6, 6));
+    assert_eq!(
+      tree.place(Cell::from_xy(10, 20)),
+      [
+        ('a', Cell::from_xy(10, 20)),
+        ('b', Cell::from_xy(13, 20)),
+        ('c', Cell::from_xy(11, 25)),
+      ]
+    );
+  }
+
+  #[test]
+  fn alignment() {
+    let row = |align| {
+      Node::row([leaf('a', 1, 5), leaf('b', 1, 2)])
+        .align(align)
+        .place(Cell::from_xy(0, 0))
+    };
+    assert_eq!(row(Align::Start)[1].1, Cell::from_xy(1, 0));
+    assert_eq!(row(Align::Center)[1].1, Cell::from_xy(1, 1));
+    assert_eq!(row(Align::End)[1].1, Cell::from_xy(1, 3));
+
+    let column = Node::column([leaf('a', 5, 1), leaf('b', 2, 1)])
+      .align(Align::Center)
+      .pad(Cell::from_xy(0, 0), Cell::from_xy(3, 0));
+    assert_eq!(column.size(), Cell::from_xy(8, 2));
+    assert_eq!(column.place(Cell::from_xy(0, 0))[1].1, Cell::from_xy(1, 1));
+  }
+}
@@ -0,0 +1,167 @@
+//! A fake [`Tty`] for tests, which draws into memory instead of a terminal.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io;
+use std::mem;
+use std::time::Duration;
+
+use crate::term::texel::Style;
+use crate::term::texel::Texel;
+use crate::term::tty::sgr;
+use crate::term::Cell;
+use crate::term::Event;
+use crate::term::Tty;
+
+/// A [`Tty`] backed by a grid of texels.
+///
+/// Writes land on the grid the same way they would land on a real terminal,
+/// and `poll()` hands out a scripted list of events, one per call. Polling an
+/// [`Event::Winch`] resizes the grid, blanking it.
+pub struct MockTty {
+  viewport: Cell,
+  screen: Vec<Texel>,
+  events: VecDeque<Event>,
+  /// Whether `init()` has been called without a matching `fini()`.
+  pub initialized: bool,
+}
+
+impl MockTty {
+  /// Creates a blank screen of the given size, with no events to replay.
+  pub fn new(viewport: Cell) -> Self {
+    let (x, y) = viewport.xy();
+    Self {
+      viewport,
+      screen: vec![Texel::empty(); x * y],
+      events: VecDeque::new(),
+      initialized: false,
+    }
+  }
+
+  /// Adds `events` to the end of the script that `poll()` replays.
+  pub fn script(&mut self, events: impl IntoIterator<Item = Event>) {
+    self.events.extend(events);
+  }
+
+  /// Returns the number of scripted events that haven't been polled yet.
+  pub fn pending(&self) -> usize {
+    self.events.len()
+  }
+
+  /// Returns the texel at `cell`, or `None` if it's off the screen.
+  pub fn texel_at(&self, cell: Cell) -> Option<Texel> {
+    let (x, y) = self.viewport.xy();
+    if cell.col() >= x || cell.row() >= y {
+      return None;
+    }
+    Some(self.screen[cell.row() * x + cell.col()])
+  }
+
+  /// Returns the screen as plain text, one line per row, with trailing blanks
+  /// trimmed off.
+  pub fn to_plain(&self) -> String {
+    let mut out = String::new();
+    for row in self.screen.chunks(self.viewport.col()) {
+      let line = row
+        .iter()
+        .map(|tx| tx.glyph().unwrap_or(' '))
+        .collect::<String>();
+      let _ = writeln!(out, "{}", line.trim_end());
+    }
+    out
+  }
+
+  /// Returns the screen as text with SGR escapes, one line per row.
+  ///
+  /// Every change of style resets and then spells out the whole new style, so
+  /// the output only depends on what's on the screen and not on how it got
+  /// there; that makes it suitable for comparing against a golden file.
+  pub fn to_ansi(&self) -> String {
+    let mut out = String::new();
+    for row in self.screen.chunks(self.viewport.col()) {
+      let mut style = Style::new();
+      for tx in row {
+        if tx.style() != style {
+          style = tx.style();
+          out.push_str(&sgr(None, style));
+        }
+        out.push(tx.glyph().unwrap_or(' '));
+      }
+      if style != Style::new() {
+        out.push_str(&sgr(None, Style::new()));
+      }
+      out.push('\n');
+    }
+    out
+  }
+}
+
+impl Tty for MockTty {
+  fn init(&mut self) -> io::Result<()> {
+    self.initialized = true;
+    Ok(())
+  }
+
+  fn fini(&mut self) -> io::Result<()> {
+    self.initialized = false;
+    Ok(())
+  }
+
+  fn viewport(&mut self) -> io::Result<Cell> {
+    Ok(self.viewport)
+  }
+
+  fn poll(&mut self, _: Option<Duration>) -> io::Result<Option<Event>> {
+    let event = self.events.pop_front();
+    if let Some(Event::Winch(viewport)) = event {
+      *self = Self {
+        events: mem::take(&mut self.events),
+        initialized: self.initialized,
+        ..Self::new(viewport)
+      };
+    }
+    Ok(event)
+  }
+
+  fn write(&mut self, start: Cell, texels: &[Texel]) -> io::Result<usize> {
+    let (width, height) = self.viewport.xy();
+    if start.col() >= width || start.row() >= height {
+      return Ok(0);
+    }
+
+    let len = texels.len().min(width - start.col());
+    let offset = start.row() * width + start.col();
+    self.screen[offset..offset + len].copy_from_slice(&texels[..len]);
+    Ok(len)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::term::with_tty;
+
+  #[test]
+  fn write_clips_to_row() {
+    let mut tty = MockTty::new(Cell::from_xy(4, 2));
+    let texels = Style::new().texels_from_str("hello").collect::<Vec<_>>();
+    assert_eq!(tty.write(Cell::from_xy(1, 1), &texels).unwrap(), 3);
+    assert_eq!(tty.write(Cell::from_xy(0, 2), &texels).unwrap(), 0);
+    assert_eq!(tty.texel_at(Cell::from_xy(3, 1)), Some(Texel::new('l')));
+    assert_eq!(tty.to_plain(), "\n hel\n");
+  }
+
+  #[test]
+  fn with_tty_pairs_init_and_fini() {
+    let mut tty = MockTty::new(Cell::from_xy(4, 2));
+    let inside = with_tty(&mut tty, |tty| tty.viewport()).unwrap();
+    assert_eq!(inside, Cell::from_xy(4, 2));
+    assert!(!tty.initialized);
+
+    let result = with_tty(&mut tty, |_| -> io::Result<()> {
+      Err(io::Error::other("oops"))
+    });
+    assert!(result.is_err());
+    assert!(!tty.initialized);
+  }
+}
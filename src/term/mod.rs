@@ -1,9 +1,14 @@
 //! Building blocks for the TUI.
 
 mod canvas;
+#[cfg(test)]
+mod mock;
 mod tty;
 
+pub mod layout;
 pub mod texel;
 
 pub use canvas::*;
+#[cfg(test)]
+pub use mock::*;
 pub use tty::*;
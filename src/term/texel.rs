@@ -1,36 +1,113 @@
 //! "Terminal elements" or texels, analogous to a pixel or voxel.
 
+use std::str::FromStr;
+
 use enumflags2::BitFlags;
 
-/// A character color, representing the standard ANSI colors.
+/// A character color: one of the standard ANSI colors, a color from the
+/// 256-color palette, or a 24-bit color.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[allow(missing_docs)]
 #[rustfmt::skip]
 pub enum Color {
   DkBlack, DkRed, DkGreen, DkYellow, DkBlue, DkMagenta, DkCyan, DkWhite,
   LtBlack, LtRed, LtGreen, LtYellow, LtBlue, LtMagenta, LtCyan, LtWhite,
+  /// An index into the 256-color palette, the first 16 of which are the ANSI
+  /// colors.
+  Indexed(u8),
+  /// A 24-bit color.
+  Rgb(u8, u8, u8),
 }
 
+/// The ANSI colors, in the order of their indices in the 256-color palette.
+#[rustfmt::skip]
+const ANSI: [Color; 16] = [
+  Color::DkBlack, Color::DkRed, Color::DkGreen, Color::DkYellow,
+  Color::DkBlue, Color::DkMagenta, Color::DkCyan, Color::LtBlack,
+  Color::DkWhite, Color::LtRed, Color::LtGreen, Color::LtYellow,
+  Color::LtBlue, Color::LtMagenta, Color::LtCyan, Color::LtWhite,
+];
+
+/// The names of the colors in `ANSI`, as they are written in theme files.
+#[rustfmt::skip]
+const ANSI_NAMES: [&str; 16] = [
+  "dk-black", "dk-red", "dk-green", "dk-yellow",
+  "dk-blue", "dk-magenta", "dk-cyan", "lt-black",
+  "dk-white", "lt-red", "lt-green", "lt-yellow",
+  "lt-blue", "lt-magenta", "lt-cyan", "lt-white",
+];
+
+/// The usual (xterm) RGB values of the colors in `ANSI`. Terminals are free to
+/// pick their own, so these are only good for estimating which is nearest.
+#[rustfmt::skip]
+const ANSI_RGB: [(u8, u8, u8); 16] = [
+  (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+  (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+  (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+  (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+/// The levels each channel can take in the 6x6x6 cube of the 256-color
+/// palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
 impl Color {
-  pub(crate) fn to_crossterm(self) -> crossterm::style::Color {
-    use crossterm::style::Color;
+  /// Returns this color's approximate RGB value.
+  pub fn to_rgb(self) -> (u8, u8, u8) {
     match self {
-      Self::DkBlack => Color::Black,
-      Self::DkRed => Color::DarkRed,
-      Self::DkGreen => Color::DarkGreen,
-      Self::DkYellow => Color::DarkYellow,
-      Self::DkBlue => Color::DarkBlue,
-      Self::DkMagenta => Color::DarkMagenta,
-      Self::DkCyan => Color::DarkCyan,
-      Self::DkWhite => Color::DarkGrey,
-      Self::LtBlack => Color::Grey,
-      Self::LtRed => Color::Red,
-      Self::LtGreen => Color::Green,
-      Self::LtYellow => Color::Yellow,
-      Self::LtBlue => Color::Blue,
-      Self::LtMagenta => Color::Magenta,
-      Self::LtCyan => Color::Cyan,
-      Self::LtWhite => Color::White,
+      Self::Rgb(r, g, b) => (r, g, b),
+      Self::Indexed(i @ 0..=15) => ANSI_RGB[i as usize],
+      Self::Indexed(i @ 16..=231) => {
+        let i = (i - 16) as usize;
+        (
+          CUBE_LEVELS[i / 36],
+          CUBE_LEVELS[i / 6 % 6],
+          CUBE_LEVELS[i % 6],
+        )
+      }
+      Self::Indexed(i) => {
+        let level = 8 + (i - 232) * 10;
+        (level, level, level)
+      }
+      ansi => {
+        let i = ANSI.iter().position(|&c| c == ansi).unwrap();
+        ANSI_RGB[i]
+      }
+    }
+  }
+
+  /// Reduces this color to the nearest one that `palette` can show.
+  pub fn reduce(self, palette: Palette) -> Color {
+    match (self, palette) {
+      (_, Palette::TrueColor) => self,
+      (Self::Rgb(r, g, b), Palette::Indexed) => nearest_indexed((r, g, b)),
+      (_, Palette::Indexed) => self,
+      (Self::Indexed(i @ 0..=15), Palette::Ansi) => ANSI[i as usize],
+      (Self::Indexed(_) | Self::Rgb(..), Palette::Ansi) => {
+        nearest(self.to_rgb(), ANSI.iter().copied())
+      }
+      (ansi, Palette::Ansi) => ansi,
+    }
+  }
+
+  /// Returns the SGR parameters that select this color, as either the
+  /// foreground or the background.
+  ///
+  /// The ANSI colors get their short codes, such as `96`, rather than the
+  /// `38;5;14` that crossterm would send for them.
+  pub(crate) fn sgr(self, background: bool) -> String {
+    let base = if background { 40 } else { 30 };
+    match self {
+      Self::Indexed(i) => format!("{};5;{i}", base + 8),
+      Self::Rgb(r, g, b) => format!("{};2;{r};{g};{b}", base + 8),
+      ansi => {
+        let i = ANSI.iter().position(|&c| c == ansi).unwrap();
+        if i < 8 {
+          format!("{}", base + i)
+        } else {
+          format!("{}", base + 60 + i - 8)
+        }
+      }
     }
   }
 
@@ -45,6 +122,95 @@ impl Color {
   }
 }
 
+impl FromStr for Color {
+  type Err = String;
+
+  /// Parses a color: either the name of an ANSI color, like `lt-cyan`; an index
+  /// into the 256-color palette; or a hex code, like `#2e8b57`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if let Some(i) = ANSI_NAMES.iter().position(|&name| name == s) {
+      return Ok(ANSI[i]);
+    }
+    if let Ok(i) = s.parse::<u8>() {
+      return Ok(Self::Indexed(i));
+    }
+    if let Some(hex) = s.strip_prefix('#').filter(|h| h.len() == 6) {
+      if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+        let [_, r, g, b] = rgb.to_be_bytes();
+        return Ok(Self::Rgb(r, g, b));
+      }
+    }
+    Err(format!(
+      "invalid color `{s}`; expected a name like `lt-cyan`, an index from 0 \
+       to 255, or a hex code like `#2e8b57`"
+    ))
+  }
+}
+
+/// The colors a terminal is able to show.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Palette {
+  /// Only the 16 ANSI colors.
+  #[default]
+  Ansi,
+  /// The 256-color palette.
+  Indexed,
+  /// Any 24-bit color.
+  TrueColor,
+}
+
+impl Palette {
+  /// Guesses the terminal's palette from the environment, the same way most
+  /// terminal programs do.
+  pub fn detect() -> Self {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    Self::from_env(&var("COLORTERM"), &var("TERM"))
+  }
+
+  /// Guesses the palette from the values of `COLORTERM` and `TERM`.
+  fn from_env(colorterm: &str, term: &str) -> Self {
+    if colorterm == "truecolor" || colorterm == "24bit" {
+      Self::TrueColor
+    } else if term.contains("256color") {
+      Self::Indexed
+    } else {
+      Self::Ansi
+    }
+  }
+}
+
+/// Returns the color in `candidates` closest to `rgb`.
+fn nearest(
+  rgb: (u8, u8, u8),
+  candidates: impl Iterator<Item = Color>,
+) -> Color {
+  let distance = |c: Color| {
+    let (r, g, b) = c.to_rgb();
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+    d(r, rgb.0) + d(g, rgb.1) + d(b, rgb.2)
+  };
+  candidates.min_by_key(|&c| distance(c)).unwrap()
+}
+
+/// Returns the color in the 256-color palette closest to `rgb`, from either
+/// the color cube or the grayscale ramp.
+fn nearest_indexed(rgb: (u8, u8, u8)) -> Color {
+  let level = |x: u8| {
+    (0..6)
+      .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - x as i32).abs())
+      .unwrap() as u8
+  };
+  let cube = 16 + 36 * level(rgb.0) + 6 * level(rgb.1) + level(rgb.2);
+
+  let mean = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+  let gray = 232 + ((mean.saturating_sub(3) / 10).min(23)) as u8;
+
+  nearest(
+    rgb,
+    [Color::Indexed(cube), Color::Indexed(gray)].into_iter(),
+  )
+}
+
 /// A character weight, ranging from light to bold.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[allow(missing_docs)]
@@ -60,12 +226,15 @@ enum Meta {
   Bold = 1 << 0,
   Dim = 1 << 1,
   Uline = 1 << 2,
+  Italic = 1 << 3,
+  Reverse = 1 << 4,
+  Strike = 1 << 5,
 
   BgReset = 1 << 8,
   FgReset = 1 << 9,
 }
 
-/// A texel style, including color and weight.
+/// A texel style, including color, weight, and other text attributes.
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Style {
   fg: Color,
@@ -148,23 +317,74 @@ impl Style {
     self
   }
 
-  /// Returns fg, bg, and weight that are different from self going to into.
+  /// Returns whether text is underlined.
   #[inline]
-  pub(crate) fn diff(
-    self,
-    into: Self,
-  ) -> (Option<Option<Color>>, Option<Option<Color>>, Option<Weight>) {
-    let mut res = (None, None, None);
-    if self.fg() != into.fg() {
-      res.0 = Some(into.fg());
-    }
-    if self.bg() != into.bg() {
-      res.1 = Some(into.bg());
+  pub fn underline(self) -> bool {
+    self.meta.contains(Meta::Uline)
+  }
+
+  /// Returns a copy of this style with underlining turned on or off.
+  #[inline]
+  pub fn with_underline(self, on: bool) -> Self {
+    self.with_meta(Meta::Uline, on)
+  }
+
+  /// Returns whether text is italic.
+  #[inline]
+  pub fn italic(self) -> bool {
+    self.meta.contains(Meta::Italic)
+  }
+
+  /// Returns a copy of this style with italics turned on or off.
+  #[inline]
+  pub fn with_italic(self, on: bool) -> Self {
+    self.with_meta(Meta::Italic, on)
+  }
+
+  /// Returns whether the foreground and background colors are swapped.
+  #[inline]
+  pub fn reverse(self) -> bool {
+    self.meta.contains(Meta::Reverse)
+  }
+
+  /// Returns a copy of this style with reverse video turned on or off.
+  #[inline]
+  pub fn with_reverse(self, on: bool) -> Self {
+    self.with_meta(Meta::Reverse, on)
+  }
+
+  /// Returns whether text is struck through.
+  #[inline]
+  pub fn strikethrough(self) -> bool {
+    self.meta.contains(Meta::Strike)
+  }
+
+  /// Returns a copy of this style with strikethrough turned on or off.
+  #[inline]
+  pub fn with_strikethrough(self, on: bool) -> Self {
+    self.with_meta(Meta::Strike, on)
+  }
+
+  fn with_meta(mut self, meta: Meta, on: bool) -> Self {
+    self.meta.set(meta, on);
+    self
+  }
+
+  /// Returns the parts of `into` that are different from self.
+  #[inline]
+  pub(crate) fn diff(self, into: Self) -> Diff {
+    fn changed<T: PartialEq>(from: T, to: T) -> Option<T> {
+      (from != to).then_some(to)
     }
-    if self.weight() != into.weight() {
-      res.2 = Some(into.weight());
+    Diff {
+      fg: changed(self.fg(), into.fg()),
+      bg: changed(self.bg(), into.bg()),
+      weight: changed(self.weight(), into.weight()),
+      underline: changed(self.underline(), into.underline()),
+      italic: changed(self.italic(), into.italic()),
+      reverse: changed(self.reverse(), into.reverse()),
+      strikethrough: changed(self.strikethrough(), into.strikethrough()),
     }
-    res
   }
 
   /// Returns an iterator over a series of texels built out of the characters
@@ -174,6 +394,19 @@ impl Style {
   }
 }
 
+/// The changes needed to go from one [`Style`] to another; `None` means that
+/// part stays the same.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) struct Diff {
+  pub fg: Option<Option<Color>>,
+  pub bg: Option<Option<Color>>,
+  pub weight: Option<Weight>,
+  pub underline: Option<bool>,
+  pub italic: Option<bool>,
+  pub reverse: Option<bool>,
+  pub strikethrough: Option<bool>,
+}
+
 impl Default for Style {
   fn default() -> Self {
     Self::new()
@@ -284,3 +517,79 @@ impl<C: Into<char>> FromChar for C {
     Texel::new(self.into()).with_style(style)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rgb_to_indexed() {
+    let cases = [
+      ((0, 0, 0), 16),
+      ((255, 255, 255), 231),
+      ((255, 0, 0), 196),
+      ((95, 135, 175), 67),
+      ((100, 140, 170), 67),
+      // The ends of the grey ramp, and just past them, where the cube's black
+      // and white are closer.
+      ((8, 8, 8), 232),
+      ((3, 3, 3), 16),
+      ((238, 238, 238), 255),
+      ((248, 248, 248), 231),
+      // Greys between the cube's levels are closer to the ramp.
+      ((128, 128, 128), 244),
+      ((120, 125, 130), 244),
+    ];
+    for (rgb, index) in cases {
+      let (r, g, b) = rgb;
+      assert_eq!(
+        Color::Rgb(r, g, b).reduce(Palette::Indexed),
+        Color::Indexed(index),
+        "{rgb:?}",
+      );
+    }
+    assert_eq!(
+      Color::Indexed(100).reduce(Palette::Indexed),
+      Color::Indexed(100)
+    );
+    assert_eq!(
+      Color::Rgb(1, 2, 3).reduce(Palette::TrueColor),
+      Color::Rgb(1, 2, 3)
+    );
+  }
+
+  #[test]
+  fn to_ansi() {
+    let cases = [
+      (Color::Rgb(250, 5, 5), Color::LtRed),
+      (Color::Rgb(200, 0, 0), Color::DkRed),
+      (Color::Rgb(0, 0, 240), Color::DkBlue),
+      (Color::Indexed(9), Color::LtRed),
+      (Color::Indexed(196), Color::LtRed),
+      (Color::Indexed(16), Color::DkBlack),
+      (Color::Indexed(231), Color::LtWhite),
+      (Color::Indexed(232), Color::DkBlack),
+      (Color::Indexed(244), Color::DkWhite),
+      (Color::Indexed(255), Color::LtBlack),
+      (Color::DkGreen, Color::DkGreen),
+    ];
+    for (color, ansi) in cases {
+      assert_eq!(color.reduce(Palette::Ansi), ansi, "{color:?}");
+    }
+  }
+
+  #[test]
+  fn detect_palette() {
+    let cases = [
+      ("truecolor", "xterm-256color", Palette::TrueColor),
+      ("24bit", "", Palette::TrueColor),
+      ("", "xterm-256color", Palette::Indexed),
+      ("yes", "screen-256color", Palette::Indexed),
+      ("", "xterm", Palette::Ansi),
+      ("", "", Palette::Ansi),
+    ];
+    for (colorterm, term, palette) in cases {
+      assert_eq!(Palette::from_env(colorterm, term), palette, "{term}");
+    }
+  }
+}
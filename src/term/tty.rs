@@ -8,7 +8,8 @@ use std::time::Duration;
 
 use enumflags2::BitFlags;
 
-use crate::term::texel::Color;
+use crate::term::texel::Palette;
+use crate::term::texel::Style;
 use crate::term::texel::Texel;
 use crate::term::texel::Weight;
 
@@ -41,6 +42,21 @@ pub trait Tty {
   ///
   /// Returns the number of texels drawn.
   fn write(&mut self, start: Cell, texels: &[Texel]) -> io::Result<usize>;
+
+  /// Starts a frame: writes until the matching [`Tty::end_frame()`] may be
+  /// held back, and should appear on the screen all at once.
+  fn begin_frame(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+
+  /// Ends a frame, making sure everything written since
+  /// [`Tty::begin_frame()`] reaches the screen.
+  ///
+  /// Returns the number of bytes the frame took to send, or zero if that
+  /// isn't known.
+  fn end_frame(&mut self) -> io::Result<usize> {
+    Ok(0)
+  }
 }
 impl dyn Tty {} // Object safe.
 
@@ -83,12 +99,6 @@ impl Cell {
     Self(tty_coords.0 as usize, tty_coords.1 as usize)
   }
 
-  /// Creates a new cell out of one-indexed, half-word tty coordinates.
-  fn to_tty(self) -> (u16, u16) {
-    debug_assert!(self.0 < u16::MAX as usize && self.1 < u16::MAX as usize);
-    (self.0 as u16, self.1 as u16)
-  }
-
   /// Returns this cell's row.
   pub fn row(self) -> usize {
     self.1
@@ -179,9 +189,49 @@ pub enum Mod {
 #[derive(Default, Clone)]
 pub struct AnsiTty {
   pub mouse_capture: bool,
+  /// The colors the terminal can show; anything else is reduced to the
+  /// nearest color it can.
+  pub palette: Palette,
+  /// Whether to wrap each frame in synchronized-update mode, so that the
+  /// terminal shows it in one go rather than as it trickles in.
+  pub synchronized_output: bool,
+
+  /// Output for the frame being drawn, sent to the terminal all at once when
+  /// it ends.
+  buf: Vec<u8>,
+  /// Whether we're between `begin_frame()` and `end_frame()`.
+  in_frame: bool,
+  /// The size of the terminal, as of the last time we checked or were told it
+  /// changed.
+  viewport: Option<Cell>,
+  /// Where the terminal's cursor is, if we know.
+  cursor: Option<Cell>,
+  /// The style the terminal will draw the next glyph with, if we know.
+  pen: Option<Style>,
 }
 
 impl AnsiTty {
+  /// Creates a new `AnsiTty` with the given settings.
+  pub fn new(
+    mouse_capture: bool,
+    palette: Palette,
+    synchronized_output: bool,
+  ) -> Self {
+    Self {
+      mouse_capture,
+      palette,
+      synchronized_output,
+      ..Self::default()
+    }
+  }
+
+  /// Guesses whether the terminal understands synchronized-update mode (DEC
+  /// private mode 2026), from the environment.
+  pub fn detect_synchronized_output() -> bool {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    synchronized_output_from_env(&var("TERM"), &var("TERM_PROGRAM"))
+  }
+
   /// Installs a panic handler that reverses the alternate screen before
   /// anything is printed.
   pub fn install_panic_hook(&self) {
@@ -193,6 +243,187 @@ impl AnsiTty {
       hook(info);
     }));
   }
+
+  /// Sends everything written so far to the terminal, returning the number of
+  /// bytes sent.
+  fn flush(&mut self) -> io::Result<usize> {
+    if self.buf.is_empty() {
+      return Ok(0);
+    }
+
+    let mut stdout = io::stdout().lock();
+    let mut bytes = self.buf.len();
+    if self.synchronized_output {
+      stdout.write_all(SYNC_BEGIN)?;
+      bytes += SYNC_BEGIN.len();
+    }
+    stdout.write_all(&self.buf)?;
+    if self.synchronized_output {
+      stdout.write_all(SYNC_END)?;
+      bytes += SYNC_END.len();
+    }
+    self.buf.clear();
+    stdout.flush()?;
+    Ok(bytes)
+  }
+
+  /// Moves the cursor to `to` by whichever sequence is shortest.
+  fn move_to(&mut self, to: Cell) {
+    if self.cursor != Some(to) {
+      let seq = cursor_move(self.cursor, to);
+      self.buf.extend_from_slice(seq.as_bytes());
+      self.cursor = Some(to);
+    }
+  }
+
+  /// Switches the pen to `style` by whichever sequence is shortest.
+  fn set_pen(&mut self, style: Style) {
+    let reduce = |s: Style| {
+      s.with_fg(s.fg().map(|c| c.reduce(self.palette)))
+        .with_bg(s.bg().map(|c| c.reduce(self.palette)))
+    };
+    let style = reduce(style);
+    if self.pen == Some(style) {
+      return;
+    }
+
+    // Going back to the default style and building the new one up from
+    // scratch is sometimes shorter than undoing the old one piece by piece,
+    // and it's the only option if we don't know what the pen is.
+    let mut seq = sgr(None, style);
+    if let Some(pen) = self.pen {
+      let diff = sgr(Some(pen), style);
+      if diff.len() < seq.len() {
+        seq = diff;
+      }
+    }
+    self.buf.extend_from_slice(seq.as_bytes());
+    self.pen = Some(style);
+  }
+}
+
+/// Guesses whether the terminal supports synchronized updates from the values
+/// of `TERM` and `TERM_PROGRAM`.
+///
+/// Only terminals that are known to support them count. Asking the terminal
+/// would be more thorough, but a terminal that doesn't answer could only be
+/// told apart from a slow one by waiting on it at every startup.
+fn synchronized_output_from_env(term: &str, term_program: &str) -> bool {
+  const TERMS: &[&str] = &[
+    "alacritty",
+    "contour",
+    "foot",
+    "ghostty",
+    "kitty",
+    "rio",
+    "wezterm",
+  ];
+  const PROGRAMS: &[&str] =
+    &["WezTerm", "ghostty", "iTerm.app", "vscode", "contour"];
+  TERMS.iter().any(|t| term.contains(t)) || PROGRAMS.contains(&term_program)
+}
+
+/// Starts a synchronized update (DEC private mode 2026).
+const SYNC_BEGIN: &[u8] = b"\x1b[?2026h";
+/// Ends a synchronized update.
+const SYNC_END: &[u8] = b"\x1b[?2026l";
+
+/// Returns the shortest sequence that moves the cursor from `from`, if known,
+/// to `to`.
+///
+/// The candidates are an absolute move (CUP), and a relative move up or down
+/// followed by a carriage return, a relative move left or right, or a move to
+/// an absolute column (CHA).
+fn cursor_move(from: Option<Cell>, to: Cell) -> String {
+  use std::cmp::Ordering;
+
+  // Terminal coordinates are one-indexed.
+  let (col, row) = (to.col() + 1, to.row() + 1);
+  let absolute = format!("\x1b[{row};{col}H");
+  let Some(from) = from else {
+    return absolute;
+  };
+
+  // A relative move of one can leave out the count.
+  let step = |n: usize, dir: char| match n {
+    1 => format!("\x1b[{dir}"),
+    n => format!("\x1b[{n}{dir}"),
+  };
+  let vertical = match to.row().cmp(&from.row()) {
+    Ordering::Equal => String::new(),
+    Ordering::Greater => step(to.row() - from.row(), 'B'),
+    Ordering::Less => step(from.row() - to.row(), 'A'),
+  };
+  let horizontal = match to.col().cmp(&from.col()) {
+    Ordering::Equal => String::new(),
+    _ if to.col() == 0 => "\r".to_string(),
+    Ordering::Greater => step(to.col() - from.col(), 'C'),
+    Ordering::Less => step(from.col() - to.col(), 'D'),
+  };
+  let column = format!("\x1b[{col}G");
+  let horizontal = if column.len() < horizontal.len() {
+    column
+  } else {
+    horizontal
+  };
+
+  let relative = vertical + &horizontal;
+  if relative.len() < absolute.len() {
+    relative
+  } else {
+    absolute
+  }
+}
+
+/// Returns the shortest SGR sequence that switches the pen from `from` to
+/// `to`; if `from` is `None`, the sequence starts with a reset.
+pub(super) fn sgr(from: Option<Style>, to: Style) -> String {
+  let mut params = Vec::new();
+  let from = match from {
+    Some(from) => from,
+    None => {
+      params.push("0".to_string());
+      Style::new()
+    }
+  };
+  let diff = from.diff(to);
+
+  for (change, background) in [(diff.fg, false), (diff.bg, true)] {
+    match change {
+      Some(Some(color)) => params.push(color.sgr(background)),
+      Some(None) => params.push(if background { "49" } else { "39" }.into()),
+      None => {}
+    }
+  }
+
+  // Bold and dim share a single reset, so going from one to the other has to
+  // go through normal intensity.
+  if let Some(weight) = diff.weight {
+    if from.weight() != Weight::Normal {
+      params.push("22".into());
+    }
+    match weight {
+      Weight::Normal => {}
+      Weight::Bold => params.push("1".into()),
+      Weight::Light => params.push("2".into()),
+    }
+  }
+
+  for (change, on, off) in [
+    (diff.underline, "4", "24"),
+    (diff.italic, "3", "23"),
+    (diff.reverse, "7", "27"),
+    (diff.strikethrough, "9", "29"),
+  ] {
+    if let Some(change) = change {
+      params.push(if change { on } else { off }.into());
+    }
+  }
+
+  if params.is_empty() {
+    return String::new();
+  }
+  format!("\x1b[{}m", params.join(";"))
 }
 
 impl Tty for AnsiTty {
@@ -200,6 +431,8 @@ impl Tty for AnsiTty {
     use crossterm::{cursor, event, execute, terminal};
     terminal::enable_raw_mode()?;
     execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+    self.cursor = None;
+    self.pen = None;
     if self.mouse_capture {
       execute!(io::stdout(), event::EnableMouseCapture)?;
     }
@@ -207,9 +440,14 @@ impl Tty for AnsiTty {
   }
 
   fn fini(&mut self) -> io::Result<()> {
-    use crossterm::{cursor, event, execute, terminal};
+    use crossterm::{cursor, event, execute, style, terminal};
     terminal::disable_raw_mode()?;
-    execute!(io::stdout(), terminal::LeaveAlternateScreen, cursor::Show)?;
+    execute!(
+      io::stdout(),
+      style::SetAttribute(style::Attribute::Reset),
+      terminal::LeaveAlternateScreen,
+      cursor::Show
+    )?;
     if self.mouse_capture {
       execute!(io::stdout(), event::DisableMouseCapture)?;
     }
@@ -217,8 +455,13 @@ impl Tty for AnsiTty {
   }
 
   fn viewport(&mut self) -> io::Result<Cell> {
+    if let Some(viewport) = self.viewport {
+      return Ok(viewport);
+    }
     let (x, y) = crossterm::terminal::size()?;
-    Ok(Cell::from_xy(x as usize, y as usize)) // No need to normalize to 0-idx.
+    let viewport = Cell::from_xy(x as usize, y as usize); // No need to normalize to 0-idx.
+    self.viewport = Some(viewport);
+    Ok(viewport)
   }
 
   fn poll(&mut self, timeout: Option<Duration>) -> io::Result<Option<Event>> {
@@ -285,68 +528,111 @@ impl Tty for AnsiTty {
         }))
       }
       CtEvent::Resize(column, row) => {
-        Ok(Some(Event::Winch(Cell::from_tty((column, row)))))
+        let viewport = Cell::from_tty((column, row));
+        self.viewport = Some(viewport);
+        self.cursor = None;
+        Ok(Some(Event::Winch(viewport)))
       }
     }
   }
 
   fn write(&mut self, start: Cell, texels: &[Texel]) -> io::Result<usize> {
-    use crossterm::{cursor, execute, style};
-    if texels.is_empty() {
+    let (width, height) = self.viewport()?.xy();
+    if texels.is_empty() || start.col() >= width || start.row() >= height {
       return Ok(0);
     }
 
-    let (width, height) = self.viewport()?.xy();
-    if start.col() >= width || start.row() >= height {
-      return Ok(0);
+    self.move_to(start);
+    let len = texels.len().min(width - start.col());
+    for texel in &texels[..len] {
+      self.set_pen(texel.style());
+      let mut utf8 = [0; 4];
+      let glyph = texel.glyph().unwrap_or(' ').encode_utf8(&mut utf8);
+      self.buf.extend_from_slice(glyph.as_bytes());
     }
 
-    let (x, y) = start.to_tty();
-
-    execute!(io::stdout(), cursor::MoveTo(x, y))?;
-
-    fn write_texel(
-      (fg, bg, _): (
-        Option<Option<Color>>,
-        Option<Option<Color>>,
-        Option<Weight>,
-      ),
-      c: Option<char>,
-    ) -> io::Result<()> {
-      let fg =
-        fg.map(|c| c.map(Color::to_crossterm).unwrap_or(style::Color::Reset));
-      let bg =
-        bg.map(|c| c.map(Color::to_crossterm).unwrap_or(style::Color::Reset));
-      execute!(
-        io::stdout(),
-        style::SetColors(style::Colors {
-          foreground: fg,
-          background: bg
-        })
-      )?;
-      // TODO: Weights.
-      write!(io::stdout(), "{}", c.unwrap_or(' '))
+    // Writing into the last column leaves the cursor in limbo: some terminals
+    // keep it there, and others wrap it onto the next row.
+    let end = start.col() + len;
+    self.cursor = (end < width).then(|| Cell::from_xy(end, start.row()));
+
+    if !self.in_frame {
+      self.flush()?;
     }
+    Ok(len)
+  }
 
-    let (mut x, _) = start.xy();
-    let mut prev = texels[0];
-    write_texel(
-      (Some(prev.fg()), Some(prev.bg()), Some(prev.weight())),
-      prev.glyph(),
-    )?;
-    x += 1;
+  fn begin_frame(&mut self) -> io::Result<()> {
+    // Drop whatever a frame that failed partway through left behind; since we
+    // no longer know what it did to the cursor and pen, forget those too.
+    if !self.buf.is_empty() {
+      self.buf.clear();
+      self.cursor = None;
+      self.pen = None;
+    }
+    self.in_frame = true;
+    Ok(())
+  }
 
-    for &texel in &texels[1..] {
-      if x >= width {
-        break;
-      }
+  fn end_frame(&mut self) -> io::Result<usize> {
+    self.in_frame = false;
+    self.flush()
+  }
+}
 
-      write_texel(prev.style().diff(texel.style()), texel.glyph())?;
-      prev = texel;
-      x += 1;
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::term::texel::Color;
+
+  #[test]
+  fn detect_synchronized_output() {
+    let cases = [
+      ("xterm-kitty", "", true),
+      ("foot-extra", "", true),
+      ("xterm-256color", "WezTerm", true),
+      ("xterm-256color", "iTerm.app", true),
+      ("xterm-256color", "Apple_Terminal", false),
+      ("xterm-256color", "", false),
+      ("screen", "", false),
+      ("linux", "", false),
+      ("", "", false),
+    ];
+    for (term, program, expected) in cases {
+      assert_eq!(
+        synchronized_output_from_env(term, program),
+        expected,
+        "{term} {program}"
+      );
     }
+  }
+
+  #[test]
+  fn cursor_move_picks_shortest() {
+    let at = Cell::from_xy;
+    assert_eq!(cursor_move(None, at(4, 2)), "\x1b[3;5H");
+    assert_eq!(cursor_move(Some(at(4, 2)), at(7, 2)), "\x1b[3C");
+    assert_eq!(cursor_move(Some(at(90, 2)), at(4, 2)), "\x1b[5G");
+    assert_eq!(cursor_move(Some(at(9, 2)), at(0, 3)), "\x1b[B\r");
+    assert_eq!(cursor_move(Some(at(4, 2)), at(4, 0)), "\x1b[2A");
+    assert_eq!(cursor_move(Some(at(4, 2)), at(40, 30)), "\x1b[31;41H");
+  }
 
-    io::stdout().flush()?;
-    Ok(x - start.col())
+  #[test]
+  fn sgr_picks_shortest() {
+    let bold = Style::new().with_weight(Weight::Bold);
+    let cyan = Color::LtCyan.fg();
+    assert_eq!(sgr(None, Style::new()), "\x1b[0m");
+    assert_eq!(sgr(None, cyan.with_weight(Weight::Bold)), "\x1b[0;96;1m");
+    assert_eq!(sgr(Some(cyan), bold), "\x1b[39;1m");
+    assert_eq!(
+      sgr(Some(bold), Style::new().with_weight(Weight::Light)),
+      "\x1b[22;2m"
+    );
+    assert_eq!(sgr(Some(bold), bold), "");
+    assert_eq!(
+      sgr(Some(Style::new()), Color::Indexed(29).bg()),
+      "\x1b[48;5;29m"
+    );
   }
 }